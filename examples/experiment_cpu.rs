@@ -0,0 +1,91 @@
+extern crate burn;
+extern crate simple_nerf;
+
+use simple_nerf::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    type InnerBackend = backend::NdArray;
+    type Backend = backend::Autodiff<InnerBackend>;
+
+    let device = Default::default();
+
+    let experiment = experiment::ExperimentConfig {
+        artifact_directory: "artifacts/experiment-cpu".into(),
+        artifact_fallback_to_temp_directory: true,
+        clamp_output: true,
+        collage_layout: experiment::tester::CollageLayout::Stacked,
+        color_noise_std: 0.0,
+        dataset: dataset::SimpleNerfDatasetConfig {
+            points_per_ray: 8,
+            distance_range: 2.0..6.0,
+            camera_convention: dataset::CameraConvention::OpenGL,
+            degenerate_ray_policy: dataset::DegenerateRayPolicy::Error,
+            download_retry_count: 3,
+            download_timeout_seconds: 300,
+            jitter_fraction: 1.0,
+            max_images: None,
+            max_views: None,
+            pixel_value_range: dataset::PixelValueRange::Auto,
+            sample_spacing: dataset::SampleSpacing::Uniform,
+            scene_bbox: None,
+            stratified: false,
+            use_ndc: false,
+            view_stride: 1,
+        },
+        dataset_file_path_or_url: "resources/lego-tiny/data.npz".into(),
+        distortion_weight: 0.0,
+        epoch_count: 2,
+        grad_accumulation_steps: 1,
+        learning_rate: 1e-3,
+        loss_reduction: experiment::trainer::LossReduction::Mean,
+        lpips_weights_path: None,
+        lr_schedule: experiment::trainer::LrSchedule::Constant,
+        opacity_reg_weight: 0.0,
+        output_format: experiment::tester::OutputFormat::Png,
+        profiling: experiment::trainer::ProfilingConfig {
+            epoch_interval: 25,
+            preview_interval: None,
+            psnr_downsampling_factor: 1,
+        },
+        psnr_per_channel: false,
+        reconstruction_loss: loss::ReconstructionLoss::Mse,
+        renderer: renderer::VolumeRendererConfig {
+            density_noise_std: 0.0,
+            memory_budget_bytes: None,
+            output_transform: renderer::ColorTransform::Linear,
+            scene: scene::VolumetricSceneConfig {
+                color_channel_count: 3,
+                density_activation: scene::Activation::Relu,
+                hidden_activation: scene::Activation::Relu,
+                hidden_size: 32,
+                initializer: burn::nn::Initializer::KaimingUniform {
+                    gain: 1.0 / 3.0_f64.sqrt(),
+                    fan_out_only: false,
+                },
+                direction_encoder: encoder::EncoderConfig::Positional(
+                    encoder::PositionalEncoderConfig {
+                        encoding_factor: 4,
+                        frequency_spacing: encoder::FrequencySpacing::Log2,
+                        include_input: true,
+                    },
+                ),
+                position_encoder: encoder::EncoderConfig::Positional(
+                    encoder::PositionalEncoderConfig {
+                        encoding_factor: 4,
+                        frequency_spacing: encoder::FrequencySpacing::Log2,
+                        include_input: true,
+                    },
+                ),
+            },
+        },
+        save_alpha: false,
+        seed: None,
+        train_ratio: 0.8,
+        weight_decay: 0.0,
+    }
+    .init::<Backend>(&device, true)?;
+
+    experiment.tester.test(experiment.trainer.train()?)?;
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+"""
+Convert the pretrained LPIPS network (AlexNet, version 0.1) into the NPZ
+archive loaded by `LpipsMetric::init_from_weights`.
+
+Usage:
+    pip install lpips numpy
+    python resources/lpips-alex/convert.py resources/lpips-alex/weights.npz
+
+The archive has the following `float32` arrays, where `index` is the tapped
+layer in `0..5`:
+    - `features.{index}.weight`: `[C, C_in, kernel, kernel]`
+    - `features.{index}.bias`: `[C]`
+    - `linears.{index}.weight`: `[1, C, 1, 1]`
+"""
+
+import sys
+
+import lpips
+import numpy as np
+
+# The tapped convolutions of `torchvision.models.alexnet().features`
+FEATURE_LAYERS = [
+    ("slice1", 0),
+    ("slice2", 3),
+    ("slice3", 6),
+    ("slice4", 8),
+    ("slice5", 10),
+]
+
+
+def main(file_path: str) -> None:
+    state = lpips.LPIPS(net="alex", version="0.1").state_dict()
+    arrays = {}
+    for index, (name, layer) in enumerate(FEATURE_LAYERS):
+        for parameter in ["weight", "bias"]:
+            arrays[f"features.{index}.{parameter}"] = state[
+                f"net.{name}.{layer}.{parameter}"
+            ]
+        arrays[f"linears.{index}.weight"] = state[f"lin{index}.model.1.weight"]
+
+    np.savez(
+        file_path,
+        **{
+            name: tensor.detach().cpu().numpy().astype(np.float32)
+            for name, tensor in arrays.items()
+        },
+    )
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])
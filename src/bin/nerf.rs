@@ -0,0 +1,74 @@
+extern crate anyhow;
+extern crate burn;
+extern crate simple_nerf;
+
+use anyhow::{anyhow, bail, Result};
+use burn::tensor::backend::Backend;
+use simple_nerf::prelude::*;
+use std::{env, path::PathBuf};
+
+const USAGE: &str =
+    "Usage: nerf --config <path> [--device cpu|wgpu] [--clear-artifacts]";
+
+// NOTE: The config is the JSON of `ExperimentConfig`,
+// such as the `experiment.json` saved in the artifacts
+fn main() -> Result<()> {
+    let mut config_path = None;
+    let mut device = "wgpu".to_string();
+    let mut do_clear_artifacts_directory = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = args.next(),
+            "--device" => {
+                device = args.next().ok_or(anyhow!("Device is missing"))?
+            },
+            "--clear-artifacts" => do_clear_artifacts_directory = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+            _ => bail!("Unknown argument: {}\n{}", arg, USAGE),
+        }
+    }
+
+    let config_path =
+        config_path.ok_or(anyhow!("Config path is missing\n{}", USAGE))?;
+    let config =
+        experiment::ExperimentConfig::load(&config_path).map_err(|error| {
+            anyhow!("Failed to load {:?}: {}", config_path, error)
+        })?;
+
+    match device.as_str() {
+        "cpu" => run::<backend::NdArray>(&config, do_clear_artifacts_directory),
+        "wgpu" => run::<backend::Wgpu>(&config, do_clear_artifacts_directory),
+        _ => bail!("Unknown device: {}\n{}", device, USAGE),
+    }
+}
+
+fn run<B: Backend>(
+    config: &experiment::ExperimentConfig,
+    do_clear_artifacts_directory: bool,
+) -> Result<()>
+where
+    B::FloatElem: Into<f64>,
+{
+    let device = Default::default();
+
+    let experiment = config
+        .init::<backend::Autodiff<B>>(&device, do_clear_artifacts_directory)?;
+    let output = experiment.tester.test(experiment.trainer.train()?)?;
+
+    let artifact_directory = PathBuf::from(&config.artifact_directory);
+    println!("Artifacts: {:?}", artifact_directory);
+    println!(
+        "Evaluation output: {:?}",
+        artifact_directory.join("evaluation-output.json")
+    );
+    if let Some(collage_path) = output.collage_path {
+        println!("Collage: {:?}", collage_path);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use crate::*;
+use burn::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Config, Debug)]
+pub struct RenderCacheConfig {
+    #[config(default = 16)]
+    pub capacity: usize,
+    #[config(default = 1e-4)]
+    pub pose_tolerance: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderCache<B: Backend> {
+    capacity: usize,
+    entries: HashMap<RenderCacheKey, Tensor<B, 3>>,
+    hit_count: usize,
+    miss_count: usize,
+    pose_tolerance: f32,
+    recencies: VecDeque<RenderCacheKey>,
+    renderer: renderer::VolumeRenderer<B>,
+}
+
+// NOTE: The rays are built from the pose by the dataset config, so the
+// config and the ray shape are in the key besides the quantized pose
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct RenderCacheKey {
+    dataset: String,
+    pose: Vec<i64>,
+    shape: [usize; 4],
+}
+
+impl RenderCacheConfig {
+    pub fn init<B: Backend>(
+        &self,
+        renderer: renderer::VolumeRenderer<B>,
+    ) -> RenderCache<B> {
+        RenderCache {
+            capacity: self.capacity.max(1),
+            entries: HashMap::new(),
+            hit_count: 0,
+            miss_count: 0,
+            pose_tolerance: self.pose_tolerance,
+            recencies: VecDeque::new(),
+            renderer,
+        }
+    }
+}
+
+impl<B: Backend> RenderCache<B> {
+    pub fn forward(
+        &mut self,
+        pose: [[f32; 4]; 4],
+        dataset: &dataset::SimpleNerfDatasetConfig,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+    ) -> Tensor<B, 3> {
+        let key = self.get_key(&pose, dataset, positions.dims());
+
+        if let Some(image) = self.entries.get(&key) {
+            self.hit_count += 1;
+            let image = image.clone();
+            self.recencies.retain(|recent| recent != &key);
+            self.recencies.push_back(key);
+            return image;
+        }
+
+        self.miss_count += 1;
+        let image = self.renderer.forward(directions, intervals, positions);
+        if self.entries.len() >= self.capacity {
+            if let Some(oldest) = self.recencies.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key.clone(), image.clone());
+        self.recencies.push_back(key);
+
+        image
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recencies.clear();
+    }
+
+    pub fn get_hit_count(&self) -> usize {
+        self.hit_count
+    }
+
+    pub fn get_miss_count(&self) -> usize {
+        self.miss_count
+    }
+
+    pub fn get_renderer(&self) -> &renderer::VolumeRenderer<B> {
+        &self.renderer
+    }
+
+    // NOTE: Any change of the weights invalidates the rendered images
+    pub fn set_renderer(
+        &mut self,
+        renderer: renderer::VolumeRenderer<B>,
+    ) {
+        self.renderer = renderer;
+        self.clear();
+    }
+
+    fn get_key(
+        &self,
+        pose: &[[f32; 4]; 4],
+        dataset: &dataset::SimpleNerfDatasetConfig,
+        shape: [usize; 4],
+    ) -> RenderCacheKey {
+        let tolerance = self.pose_tolerance.max(f32::EPSILON);
+        RenderCacheKey {
+            dataset: dataset.to_string(),
+            pose: pose
+                .iter()
+                .flatten()
+                .map(|value| (value / tolerance).round() as i64)
+                .collect(),
+            shape,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::tensor::Distribution;
+
+    type Backend = burn::backend::NdArray;
+
+    #[test]
+    fn render_cache_hits_and_misses() {
+        let device = Default::default();
+
+        let get_renderer = || {
+            renderer::VolumeRendererConfig::new(scene::tests::get_test_config(
+                2, 8,
+            ))
+            .init::<Backend>(&device)
+            .unwrap()
+        };
+        let mut cache = RenderCacheConfig::new().init(get_renderer());
+
+        let dataset = dataset::SimpleNerfDatasetConfig::new(3, 2.0..6.0);
+        let get_rays = |points_per_ray| {
+            let shape = [4, 4, points_per_ray, 3];
+            (
+                Tensor::random(shape, Distribution::Default, &device),
+                Tensor::random(
+                    [4, 4, points_per_ray, 1],
+                    Distribution::Default,
+                    &device,
+                ),
+                Tensor::random(shape, Distribution::Default, &device),
+            )
+        };
+        let (directions, intervals, positions) = get_rays(3);
+        let pose = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 4.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+
+        let image = cache.forward(
+            pose,
+            &dataset,
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (0, 1));
+
+        let image_cached = cache.forward(
+            pose,
+            &dataset,
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (1, 1));
+        assert!(image.equal(image_cached).all().into_scalar());
+
+        let mut pose_other = pose;
+        pose_other[2][3] = 3.0;
+        cache.forward(
+            pose_other,
+            &dataset,
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (1, 2));
+
+        // The same pose misses with the rays of another shape
+        let (directions_other, intervals_other, positions_other) = get_rays(5);
+        cache.forward(
+            pose,
+            &dataset::SimpleNerfDatasetConfig {
+                points_per_ray: 5,
+                ..dataset.clone()
+            },
+            directions_other.clone(),
+            intervals_other.clone(),
+            positions_other.clone(),
+        );
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (1, 3));
+
+        // The same pose and shape miss with another dataset config
+        cache.forward(
+            pose,
+            &dataset.clone().with_stratified(true),
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (1, 4));
+
+        cache.set_renderer(get_renderer());
+        cache.forward(pose, &dataset, directions, intervals, positions);
+        assert_eq!((cache.get_hit_count(), cache.get_miss_count()), (1, 5));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::*;
+use burn::{data::dataset::Dataset, prelude::*};
+
+#[derive(Config, Debug)]
+pub struct WeightHistogramConfig {
+    #[config(default = 10)]
+    pub bin_count: usize,
+    #[config(default = 4)]
+    pub item_count: usize,
+}
+
+#[derive(Config, Debug)]
+pub struct WeightHistogram {
+    pub masses: Vec<f64>,
+}
+
+impl WeightHistogramConfig {
+    pub fn init(&self) -> WeightHistogram {
+        WeightHistogram {
+            masses: vec![0.0; self.bin_count.max(1)],
+        }
+    }
+
+    // NOTE: Only the first items are rendered to keep the diagnostic cheap
+    pub fn collect<B: Backend>(
+        &self,
+        renderer: &renderer::VolumeRenderer<B>,
+        dataset: &dataset::SimpleNerfDataset<B>,
+    ) -> WeightHistogram {
+        let device = renderer.devices().swap_remove(0);
+        let mut histogram = self.init();
+
+        for data in dataset.iter().take(self.item_count) {
+            let input = data.into_input(&device);
+            let weights = renderer
+                .forward_with_aux(
+                    input.directions,
+                    input.intervals,
+                    input.positions,
+                )
+                .weights;
+            histogram.accumulate(weights, input.distances);
+        }
+
+        histogram
+    }
+}
+
+impl WeightHistogram {
+    // NOTE: The depth of each sample is normalized by the first and the last
+    // distances on its ray
+    pub fn accumulate<B: Backend>(
+        &mut self,
+        weights: Tensor<B, 4>,
+        distances: Tensor<B, 4>,
+    ) {
+        let [height, width, points_per_ray, ..] = distances.dims();
+        let bin_count = self.masses.len();
+        let weights = weights.into_data().convert::<f32>().value;
+        let distances = distances.into_data().convert::<f32>().value;
+
+        for ray in 0..height * width {
+            let samples = ray * points_per_ray..(ray + 1) * points_per_ray;
+            let distances = &distances[samples.clone()];
+            let near = distances[0];
+            let length = (distances[points_per_ray - 1] - near).max(1e-10);
+
+            for (weight, distance) in weights[samples].iter().zip(distances) {
+                let depth = ((distance - near) / length).clamp(0.0, 1.0);
+                let bin =
+                    ((depth * bin_count as f32) as usize).min(bin_count - 1);
+                self.masses[bin] += *weight as f64;
+            }
+        }
+    }
+
+    pub fn get_fractions(&self) -> Vec<f64> {
+        let total = self.masses.iter().sum::<f64>().max(1e-10);
+        self.masses.iter().map(|mass| mass / total).collect()
+    }
+
+    // NOTE: Most weight in the first or the last bin suggests that
+    // the distance range is mis-set
+    pub fn is_distance_range_suspicious(&self) -> bool {
+        let fractions = self.get_fractions();
+        let edge_fraction = fractions[0]
+            + if fractions.len() > 1 {
+                fractions[fractions.len() - 1]
+            } else {
+                0.0
+            };
+        edge_fraction > 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Backend = burn::backend::NdArray;
+
+    #[test]
+    fn weight_histogram_output() {
+        let device = Default::default();
+
+        // The geometry is at the mid-depth of the rays
+        let points_per_ray = 9;
+        let distances = Tensor::<Backend, 1>::from_floats(
+            [2.0, 2.5, 3.0, 3.5, 4.0, 4.5, 5.0, 5.5, 6.0],
+            &device,
+        )
+        .reshape([1, 1, points_per_ray, 1])
+        .expand([3, 2, points_per_ray, 1]);
+        let weights = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.0, 0.0, 0.2, 0.6, 0.1, 0.0, 0.0, 0.0],
+            &device,
+        )
+        .reshape([1, 1, points_per_ray, 1])
+        .expand([3, 2, points_per_ray, 1]);
+
+        let mut histogram = WeightHistogramConfig::new().init();
+        histogram.accumulate(weights, distances);
+        let fractions = histogram.get_fractions();
+        let peak = (0..fractions.len())
+            .max_by(|&a, &b| fractions[a].total_cmp(&fractions[b]))
+            .unwrap();
+        assert_eq!(fractions.len(), 10);
+        assert_eq!(peak, 5);
+        assert!((fractions.iter().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert!(!histogram.is_distance_range_suspicious());
+
+        // The rendered weights are collected from the dataset items
+        let dataset = dataset::SimpleNerfDatasetConfig::new(4, 2.0..6.0)
+            .init_from_reader::<Backend, _>(
+                std::io::Cursor::new(dataset::tests::synthesize_test_data(
+                    3, 2, 2,
+                )),
+                &device,
+            )
+            .unwrap();
+        let renderer = renderer::VolumeRendererConfig::new(
+            scene::tests::get_test_config(2, 8),
+        )
+        .init::<Backend>(&device)
+        .unwrap();
+        let histogram = WeightHistogramConfig::new()
+            .with_bin_count(4)
+            .collect(&renderer, &dataset);
+        assert_eq!(histogram.masses.len(), 4);
+        assert!(histogram.masses.iter().all(|mass| mass.is_finite()));
+    }
+}
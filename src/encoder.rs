@@ -1,43 +1,221 @@
 use anyhow::{bail, Result};
-use burn::prelude::*;
+use burn::{module::Param, prelude::*, tensor::Distribution};
 use std::f32::consts::PI;
 
+// NOTE: The common interface of the encoders selected by `EncoderConfig`
+pub trait InputEncoder<B: Backend> {
+    fn forward(
+        &self,
+        coordinates: Tensor<B, 2>,
+    ) -> Tensor<B, 2>;
+
+    fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize;
+}
+
+#[derive(Config, Debug)]
+pub enum EncoderConfig {
+    Positional(PositionalEncoderConfig),
+    SphericalHarmonics(SphericalHarmonicsEncoderConfig),
+    HashGrid(HashGridEncoderConfig),
+}
+
+#[derive(Debug, Module)]
+pub enum Encoder<B: Backend> {
+    Positional(PositionalEncoder<B>),
+    SphericalHarmonics(SphericalHarmonicsEncoder<B>),
+    HashGrid(HashGridEncoder<B>),
+}
+
 #[derive(Config, Debug)]
 pub struct PositionalEncoderConfig {
     pub encoding_factor: usize,
+    #[config(default = "FrequencySpacing::Log2")]
+    pub frequency_spacing: FrequencySpacing,
+    #[config(default = true)]
+    pub include_input: bool,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum FrequencySpacing {
+    // NOTE: The frequencies are `2^level * PI`
+    Log2,
+    // NOTE: The frequencies are evenly spaced from `PI` to `max_freq * PI`
+    Linear {
+        max_freq: f32,
+    },
+}
+
+// NOTE: The spherical harmonics of degree `d` have `d^2` coefficients,
+// and the inputs are normalized to the unit directions
+#[derive(Config, Debug)]
+pub struct SphericalHarmonicsEncoderConfig {
+    #[config(default = 4)]
+    pub degree: usize,
+}
+
+// NOTE: The grid resolutions grow geometrically from the base to the finest
+// over the levels. The inputs are mapped from `[-bound, bound]` to the grid
+#[derive(Config, Debug)]
+pub struct HashGridEncoderConfig {
+    #[config(default = 16)]
+    pub base_resolution: usize,
+    #[config(default = 4.0)]
+    pub bound: f32,
+    #[config(default = 2)]
+    pub feature_count: usize,
+    #[config(default = 512)]
+    pub finest_resolution: usize,
+    #[config(default = 16)]
+    pub level_count: usize,
+    #[config(default = 14)]
+    pub log2_table_size: usize,
+}
+
+// NOTE: The samples are the Gaussians approximating the conical frustums,
+// so the features of each frequency are attenuated by its variance. It is
+// not selectable in `EncoderConfig` since it also takes the covariances
+#[derive(Config, Debug)]
+pub struct IntegratedPositionalEncoderConfig {
+    pub encoding_factor: usize,
+    #[config(default = "FrequencySpacing::Log2")]
+    pub frequency_spacing: FrequencySpacing,
+    #[config(default = true)]
+    pub include_input: bool,
 }
 
+// NOTE: The frequencies and the phases are constant buffers, so they are
+// not saved in the records and are rebuilt from the config on loading
 #[derive(Debug, Module)]
 pub struct PositionalEncoder<B: Backend> {
+    encoding_factor: usize,
     freqs: Tensor<B, 3>,
+    include_input: bool,
     phases: Tensor<B, 3>,
 }
 
+#[derive(Debug, Module)]
+pub struct IntegratedPositionalEncoder<B: Backend> {
+    encoder: PositionalEncoder<B>,
+}
+
+#[derive(Debug, Module)]
+pub struct SphericalHarmonicsEncoder<B: Backend> {
+    degree: usize,
+    phantom: std::marker::PhantomData<B>,
+}
+
+// NOTE: Each level has a learnable table of features
+#[derive(Debug, Module)]
+pub struct HashGridEncoder<B: Backend> {
+    bound: f32,
+    resolutions: Vec<usize>,
+    tables: Vec<Param<Tensor<B, 2>>>,
+}
+
+impl EncoderConfig {
+    pub fn init<B: Backend>(
+        &self,
+        device: &B::Device,
+    ) -> Result<Encoder<B>> {
+        Ok(match self {
+            EncoderConfig::Positional(config) => {
+                Encoder::Positional(config.init(device)?)
+            },
+            EncoderConfig::SphericalHarmonics(config) => {
+                Encoder::SphericalHarmonics(config.init(device)?)
+            },
+            EncoderConfig::HashGrid(config) => {
+                Encoder::HashGrid(config.init(device)?)
+            },
+        })
+    }
+
+    pub fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        match self {
+            EncoderConfig::Positional(config) => {
+                config.get_output_size(input_size)
+            },
+            EncoderConfig::SphericalHarmonics(config) => {
+                config.get_output_size(input_size)
+            },
+            EncoderConfig::HashGrid(config) => {
+                config.get_output_size(input_size)
+            },
+        }
+    }
+}
+
+impl<B: Backend> InputEncoder<B> for Encoder<B> {
+    fn forward(
+        &self,
+        coordinates: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        match self {
+            Encoder::Positional(encoder) => encoder.forward(coordinates),
+            Encoder::SphericalHarmonics(encoder) => {
+                encoder.forward(coordinates)
+            },
+            Encoder::HashGrid(encoder) => encoder.forward(coordinates),
+        }
+    }
+
+    fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        match self {
+            Encoder::Positional(encoder) => encoder.get_output_size(input_size),
+            Encoder::SphericalHarmonics(encoder) => {
+                encoder.get_output_size(input_size)
+            },
+            Encoder::HashGrid(encoder) => encoder.get_output_size(input_size),
+        }
+    }
+}
+
 impl PositionalEncoderConfig {
     pub fn init<B: Backend>(
         &self,
         device: &B::Device,
     ) -> Result<PositionalEncoder<B>> {
-        let encoding_factor = self.encoding_factor;
-        if encoding_factor == 0 {
-            bail!("Encoding factor must be greater than 0");
-        }
+        // NOTE: The identity encoder with zero encoding factor keeps
+        // the placeholder frequencies and phases without using them
+        let encoding_factor = self.encoding_factor.max(1);
 
         let shape = [1, 2 * encoding_factor, 1];
         let levels = Tensor::arange(0..encoding_factor as i64, device);
+        let freqs = match self.frequency_spacing {
+            FrequencySpacing::Log2 => {
+                Tensor::full([encoding_factor], 2, device).powi(levels).float()
+            },
+            FrequencySpacing::Linear {
+                max_freq,
+            } => {
+                let step = if encoding_factor > 1 {
+                    (max_freq - 1.0) / (encoding_factor - 1) as f32
+                } else {
+                    0.0
+                };
+                levels.float() * step + 1.0
+            },
+        };
         let freqs =
-            (Tensor::full([encoding_factor], 2, device).powi(levels).float()
-                * PI)
-                .unsqueeze_dim::<2>(1)
-                .repeat(1, 2)
-                .reshape(shape);
+            (freqs * PI).unsqueeze_dim::<2>(1).repeat(1, 2).reshape(shape);
         let phases = Tensor::from_floats([0.0, PI / 2.0], device)
             .unsqueeze_dim::<2>(0)
             .repeat(0, encoding_factor)
             .reshape(shape);
 
         Ok(PositionalEncoder {
+            encoding_factor: self.encoding_factor,
             freqs: freqs.clone(),
+            include_input: self.include_input,
             phases: phases.clone(),
         })
     }
@@ -46,38 +224,364 @@ impl PositionalEncoderConfig {
         &self,
         input_size: usize,
     ) -> usize {
-        input_size * (2 * self.encoding_factor + 1)
+        get_output_size(input_size, self.encoding_factor, self.include_input)
     }
 }
 
 impl<B: Backend> PositionalEncoder<B> {
+    pub fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        get_output_size(input_size, self.encoding_factor, self.include_input)
+    }
+
     pub fn forward(
         &self,
         coordinates: Tensor<B, 2>,
     ) -> Tensor<B, 2> {
+        if self.encoding_factor == 0 {
+            return coordinates;
+        }
+
         let coordinates = coordinates.unsqueeze_dim::<3>(1);
-        let features = {
-            let shape = [coordinates.dims()[0] as i32, -1];
-            Tensor::cat(
-                vec![
-                    coordinates.clone(),
-                    (coordinates * self.freqs.clone() + self.phases.clone())
-                        .sin(),
-                ],
-                1,
-            )
-            .reshape(shape)
+        let shape = [coordinates.dims()[0] as i32, -1];
+        let features = (coordinates.clone() * self.freqs.clone()
+            + self.phases.clone())
+        .sin();
+
+        if self.include_input {
+            Tensor::cat(vec![coordinates, features], 1).reshape(shape)
+        } else {
+            features.reshape(shape)
+        }
+    }
+}
+
+impl<B: Backend> InputEncoder<B> for PositionalEncoder<B> {
+    fn forward(
+        &self,
+        coordinates: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        PositionalEncoder::forward(self, coordinates)
+    }
+
+    fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        PositionalEncoder::get_output_size(self, input_size)
+    }
+}
+
+impl IntegratedPositionalEncoderConfig {
+    pub fn init<B: Backend>(
+        &self,
+        device: &B::Device,
+    ) -> Result<IntegratedPositionalEncoder<B>> {
+        Ok(IntegratedPositionalEncoder {
+            encoder: PositionalEncoderConfig {
+                encoding_factor: self.encoding_factor,
+                frequency_spacing: self.frequency_spacing,
+                include_input: self.include_input,
+            }
+            .init(device)?,
+        })
+    }
+
+    pub fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        get_output_size(input_size, self.encoding_factor, self.include_input)
+    }
+}
+
+impl<B: Backend> IntegratedPositionalEncoder<B> {
+    pub fn get_output_size(
+        &self,
+        input_size: usize,
+    ) -> usize {
+        self.encoder.get_output_size(input_size)
+    }
+
+    // NOTE: The means and the covariances are `[N, D]`, where the
+    // covariances are the diagonals. The features are scaled by
+    // `exp(-0.5 * freq^2 * variance)`, so they vanish at the high frequencies
+    // for the wide frustums
+    pub fn forward(
+        &self,
+        means: Tensor<B, 2>,
+        covs: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        let encoder = &self.encoder;
+        if encoder.encoding_factor == 0 {
+            return means;
+        }
+
+        let means = means.unsqueeze_dim::<3>(1);
+        let covs = covs.unsqueeze_dim::<3>(1);
+        let shape = [means.dims()[0] as i32, -1];
+        let features = (means.clone() * encoder.freqs.clone()
+            + encoder.phases.clone())
+        .sin()
+            * (covs * encoder.freqs.clone().powf_scalar(2.0) * -0.5).exp();
+
+        if encoder.include_input {
+            Tensor::cat(vec![means, features], 1).reshape(shape)
+        } else {
+            features.reshape(shape)
+        }
+    }
+}
+
+impl SphericalHarmonicsEncoderConfig {
+    pub fn init<B: Backend>(
+        &self,
+        _device: &B::Device,
+    ) -> Result<SphericalHarmonicsEncoder<B>> {
+        if !(1..=4).contains(&self.degree) {
+            bail!(
+                "Spherical harmonics degree should be from 1 to 4, but it is {}",
+                self.degree
+            );
+        }
+
+        Ok(SphericalHarmonicsEncoder {
+            degree: self.degree,
+            phantom: std::marker::PhantomData,
+        })
+    }
+
+    pub fn get_output_size(
+        &self,
+        _input_size: usize,
+    ) -> usize {
+        self.degree.pow(2)
+    }
+}
+
+impl<B: Backend> InputEncoder<B> for SphericalHarmonicsEncoder<B> {
+    fn forward(
+        &self,
+        coordinates: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        let [count, size] = coordinates.dims();
+        assert_eq!(size, 3, "Spherical harmonics encode 3D directions");
+
+        let norms = coordinates
+            .clone()
+            .powf_scalar(2.0)
+            .sum_dim(1)
+            .sqrt()
+            .clamp_min(1e-12);
+        let directions = coordinates / norms;
+        let x = directions.clone().slice([0..count, 0..1]);
+        let y = directions.clone().slice([0..count, 1..2]);
+        let z = directions.slice([0..count, 2..3]);
+        let xx = x.clone().powf_scalar(2.0);
+        let yy = y.clone().powf_scalar(2.0);
+        let zz = z.clone().powf_scalar(2.0);
+
+        let mut features = vec![x.ones_like() * 0.2820948];
+        if self.degree > 1 {
+            features.extend([
+                y.clone() * -0.48860252,
+                z.clone() * 0.48860252,
+                x.clone() * -0.48860252,
+            ]);
+        }
+        if self.degree > 2 {
+            features.extend([
+                x.clone() * y.clone() * 1.0925485,
+                y.clone() * z.clone() * -1.0925485,
+                (zz.clone() * 2.0 - xx.clone() - yy.clone()) * 0.31539157,
+                x.clone() * z.clone() * -1.0925485,
+                (xx.clone() - yy.clone()) * 0.54627424,
+            ]);
+        }
+        if self.degree > 3 {
+            features.extend([
+                y.clone() * (xx.clone() * 3.0 - yy.clone()) * -0.5900436,
+                x.clone() * y.clone() * z.clone() * 2.8906114,
+                y.clone()
+                    * (zz.clone() * 4.0 - xx.clone() - yy.clone())
+                    * -0.4570458,
+                z.clone()
+                    * (zz.clone() * 2.0 - xx.clone() * 3.0 - yy.clone() * 3.0)
+                    * 0.37317634,
+                x.clone() * (zz * 4.0 - xx.clone() - yy.clone()) * -0.4570458,
+                z * (xx.clone() - yy.clone()) * 1.4453057,
+                x * (xx - yy * 3.0) * -0.5900436,
+            ]);
+        }
+
+        Tensor::cat(features, 1)
+    }
+
+    fn get_output_size(
+        &self,
+        _input_size: usize,
+    ) -> usize {
+        self.degree.pow(2)
+    }
+}
+
+impl HashGridEncoderConfig {
+    pub fn init<B: Backend>(
+        &self,
+        device: &B::Device,
+    ) -> Result<HashGridEncoder<B>> {
+        // NOTE: The indexs are wrapped into the table in 32-bit integers
+        if self.level_count == 0 || self.feature_count == 0 {
+            bail!("Hash grid should have at least one level and one feature");
+        }
+        if self.log2_table_size > 19 {
+            bail!(
+                "Hash grid table size should be at most 2^19, but it is 2^{}",
+                self.log2_table_size
+            );
+        }
+        if self.base_resolution == 0
+            || self.finest_resolution < self.base_resolution
+            || self.finest_resolution > 2048
+        {
+            bail!(
+                "Hash grid resolutions should be from 1 to 2048 and \
+                increasing, but they are {} and {}",
+                self.base_resolution,
+                self.finest_resolution
+            );
+        }
+        if self.bound <= 0.0 {
+            bail!("Hash grid bound should be positive");
+        }
+
+        let growth = if self.level_count > 1 {
+            ((self.finest_resolution as f64).ln()
+                - (self.base_resolution as f64).ln())
+                / (self.level_count - 1) as f64
+        } else {
+            0.0
         };
+        let resolutions = (0..self.level_count)
+            .map(|level| {
+                ((self.base_resolution as f64) * (growth * level as f64).exp())
+                    .round() as usize
+            })
+            .collect();
+        let tables = (0..self.level_count)
+            .map(|_| {
+                Param::from_tensor(Tensor::random(
+                    [1 << self.log2_table_size, self.feature_count],
+                    Distribution::Uniform(-1e-4, 1e-4),
+                    device,
+                ))
+            })
+            .collect();
+
+        Ok(HashGridEncoder {
+            bound: self.bound,
+            resolutions,
+            tables,
+        })
+    }
+
+    pub fn get_output_size(
+        &self,
+        _input_size: usize,
+    ) -> usize {
+        self.level_count * self.feature_count
+    }
+}
+
+impl<B: Backend> InputEncoder<B> for HashGridEncoder<B> {
+    // NOTE: The features of the cell corners are interpolated in each level.
+    // The corners are indexed in the row-major order of the grid, and
+    // the indexs are wrapped around the table size as the hash
+    fn forward(
+        &self,
+        coordinates: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        let [count, size] = coordinates.dims();
+        let device = coordinates.device();
+        let coordinates =
+            ((coordinates + self.bound) / (2.0 * self.bound)).clamp(0.0, 1.0);
+
+        let mut features = vec![];
+        for (resolution, table) in self.resolutions.iter().zip(&self.tables) {
+            let [table_size, feature_count] = table.dims();
+            let wrap = |indexs: Tensor<B, 2, Int>| {
+                indexs.clone()
+                    - indexs.div_scalar(table_size as i64) * table_size as i64
+            };
+
+            let scaled = coordinates.clone() * *resolution as f32;
+            let cells = scaled
+                .clone()
+                .int()
+                .clamp_max(resolution.saturating_sub(1) as i64);
+            let fractions = scaled - cells.clone().float();
 
-        features
+            let mut level_features =
+                Tensor::<B, 2>::zeros([count, feature_count], &device);
+            for corner in 0..1_usize << size {
+                let mut indexs =
+                    Tensor::<B, 2, Int>::zeros([count, 1], &device);
+                let mut weights = Tensor::<B, 2>::ones([count, 1], &device);
+                let mut stride = 1_usize;
+                for axis in 0..size {
+                    let offset = (corner >> axis) & 1;
+                    let cell = cells.clone().slice([0..count, axis..axis + 1])
+                        + offset as i64;
+                    let fraction =
+                        fractions.clone().slice([0..count, axis..axis + 1]);
+                    weights = weights
+                        * if offset == 1 {
+                            fraction
+                        } else {
+                            fraction.neg() + 1.0
+                        };
+                    indexs = wrap(indexs + cell * stride as i64);
+                    stride = stride * (resolution + 1) % table_size;
+                }
+                level_features = level_features
+                    + table.val().select(0, indexs.squeeze(1)) * weights;
+            }
+            features.push(level_features);
+        }
+
+        Tensor::cat(features, 1)
+    }
+
+    fn get_output_size(
+        &self,
+        _input_size: usize,
+    ) -> usize {
+        self.tables.len()
+            * self.tables.first().map_or(0, |table| table.dims()[1])
+    }
+}
+
+// NOTE: The identity encoder always keeps the input
+fn get_output_size(
+    input_size: usize,
+    encoding_factor: usize,
+    include_input: bool,
+) -> usize {
+    if encoding_factor == 0 || include_input {
+        input_size * (2 * encoding_factor + 1)
+    } else {
+        input_size * 2 * encoding_factor
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use burn::record;
 
-    type Backend = burn::backend::Wgpu;
+    type Backend = burn::backend::NdArray;
 
     #[test]
     fn positional_encoder_output_shape() {
@@ -85,6 +589,8 @@ mod tests {
 
         let config = PositionalEncoderConfig {
             encoding_factor: 10,
+            frequency_spacing: FrequencySpacing::Log2,
+            include_input: true,
         };
         let model = config.init::<Backend>(&device);
         assert!(model.is_ok(), "Error: {}", model.unwrap_err());
@@ -96,6 +602,8 @@ mod tests {
 
         let config = PositionalEncoderConfig {
             encoding_factor: 4,
+            frequency_spacing: FrequencySpacing::Log2,
+            include_input: true,
         };
         let model = config.init::<Backend>(&device);
         assert!(model.is_ok(), "Error: {}", model.unwrap_err());
@@ -104,11 +612,198 @@ mod tests {
         let input = Tensor::from_floats([[1.0, -2.5, 0.5, 3.0, -5.5]], &device);
         let output = model.forward(input.clone());
         assert_eq!(output.dims()[1], config.get_output_size(input.dims()[1]));
+    }
 
-        let config_invalid = PositionalEncoderConfig {
+    #[test]
+    fn positional_encoder_linear_frequencies() {
+        let device = Default::default();
+
+        let config = PositionalEncoderConfig {
+            encoding_factor: 4,
+            frequency_spacing: FrequencySpacing::Linear {
+                max_freq: 10.0,
+            },
+            include_input: true,
+        };
+        let model = config.init::<Backend>(&device);
+        assert!(model.is_ok(), "Error: {}", model.unwrap_err());
+
+        let model = model.unwrap();
+        let freqs = model.freqs.clone().into_data().convert::<f32>().value;
+        let freqs = freqs.iter().step_by(2).map(|freq| freq / PI);
+        for (freq, freq_expected) in freqs.zip([1.0, 4.0, 7.0, 10.0]) {
+            assert!((freq - freq_expected).abs() < 1e-5, "{}", freq);
+        }
+
+        let input = Tensor::from_floats([[1.0, -2.0, 0.0]], &device);
+        let output = model.forward(input.clone());
+        assert_eq!(output.dims()[1], config.get_output_size(input.dims()[1]));
+    }
+
+    #[test]
+    fn positional_encoder_without_input() {
+        let device = Default::default();
+
+        for include_input in [true, false] {
+            let config = PositionalEncoderConfig {
+                encoding_factor: 4,
+                frequency_spacing: FrequencySpacing::Log2,
+                include_input,
+            };
+            let model = config.init::<Backend>(&device);
+            assert!(model.is_ok(), "Error: {}", model.unwrap_err());
+
+            let model = model.unwrap();
+            let input = Tensor::from_floats([[1.0, -2.5, 0.5]], &device);
+            let output = model.forward(input);
+            assert_eq!(output.dims(), [1, config.get_output_size(3)]);
+            assert_eq!(output.dims()[1], model.get_output_size(3));
+        }
+        assert_eq!(
+            PositionalEncoderConfig::new(4)
+                .with_include_input(false)
+                .get_output_size(3),
+            24
+        );
+    }
+
+    #[test]
+    fn positional_encoder_identity() {
+        let device = Default::default();
+
+        let config = PositionalEncoderConfig {
             encoding_factor: 0,
+            frequency_spacing: FrequencySpacing::Log2,
+            include_input: true,
         };
-        let model = config_invalid.init::<Backend>(&device);
-        assert!(model.is_err());
+        let model = config.init::<Backend>(&device);
+        assert!(model.is_ok(), "Error: {}", model.unwrap_err());
+
+        let model = model.unwrap();
+        let input = Tensor::from_floats(
+            [[1.0, -2.5, 0.5, 3.0, -5.5], [0.0, 0.1, -0.2, 0.3, 4.0]],
+            &device,
+        );
+        let output = model.forward(input.clone());
+        assert_eq!(output.dims(), [2, config.get_output_size(5)]);
+        assert_eq!(output.into_data(), input.into_data());
+    }
+
+    #[test]
+    fn integrated_positional_encoder_vanishing_covariances() {
+        let device = Default::default();
+
+        let config = IntegratedPositionalEncoderConfig::new(4);
+        let model = config.init::<Backend>(&device);
+        assert!(model.is_ok(), "Error: {}", model.unwrap_err());
+
+        let model = model.unwrap();
+        let model_positional =
+            PositionalEncoderConfig::new(4).init::<Backend>(&device).unwrap();
+        let means =
+            Tensor::from_floats([[1.0, -2.5, 0.5], [0.1, 0.2, -0.3]], &device);
+        let output_positional = model_positional.forward(means.clone());
+        assert_eq!(output_positional.dims(), [2, config.get_output_size(3)]);
+        assert_eq!(model.get_output_size(3), config.get_output_size(3));
+
+        let mut errors = vec![];
+        for variance in [1e-2, 1e-4, 1e-6, 0.0] {
+            let output =
+                model.forward(means.clone(), means.ones_like() * variance);
+            assert_eq!(output.dims(), output_positional.dims());
+            errors.push(
+                (output - output_positional.clone()).abs().max().into_scalar(),
+            );
+        }
+        assert!(errors.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(errors[2] < 1e-3);
+        assert_eq!(errors[3], 0.0);
+
+        // The features of the highest frequency vanish for the wide frustums
+        let output = model.forward(means.clone(), means.ones_like() * 10.0);
+        let [count, size] = output.dims();
+        assert!(
+            output.slice([0..count, size - 6..size]).abs().max().into_scalar()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn encoder_output_size() {
+        let device = Default::default();
+
+        let configs = [
+            EncoderConfig::Positional(PositionalEncoderConfig::new(4)),
+            EncoderConfig::SphericalHarmonics(
+                SphericalHarmonicsEncoderConfig::new(),
+            ),
+            EncoderConfig::SphericalHarmonics(
+                SphericalHarmonicsEncoderConfig::new().with_degree(2),
+            ),
+            EncoderConfig::HashGrid(
+                HashGridEncoderConfig::new()
+                    .with_level_count(4)
+                    .with_log2_table_size(8),
+            ),
+        ];
+        let input = Tensor::<Backend, 2>::random(
+            [5, 3],
+            Distribution::Uniform(-4.0, 4.0),
+            &device,
+        );
+        for (config, output_size) in configs.iter().zip([27, 16, 4, 8]) {
+            let encoder = config.init::<Backend>(&device);
+            assert!(encoder.is_ok(), "Error: {}", encoder.unwrap_err());
+
+            let encoder = encoder.unwrap();
+            let output = encoder.forward(input.clone());
+            assert_eq!(output.dims(), [5, config.get_output_size(3)]);
+            assert_eq!(encoder.get_output_size(3), output_size);
+        }
+
+        assert!(SphericalHarmonicsEncoderConfig::new()
+            .with_degree(5)
+            .init::<Backend>(&device)
+            .is_err());
+        assert!(HashGridEncoderConfig::new()
+            .with_log2_table_size(20)
+            .init::<Backend>(&device)
+            .is_err());
+    }
+
+    #[test]
+    fn positional_encoder_saved_and_loaded() {
+        let device = Default::default();
+
+        let config = PositionalEncoderConfig::new(4);
+        let config_linear = PositionalEncoderConfig::new(4)
+            .with_frequency_spacing(FrequencySpacing::Linear {
+                max_freq: 8.0,
+            });
+        let encoder = config.init::<Backend>(&device).unwrap();
+
+        let directory = std::env::temp_dir()
+            .join("simple-nerf-tests")
+            .join("positional_encoder_saved_and_loaded");
+        std::fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("positional-encoder");
+        let recorder = record::DefaultRecorder::new();
+        config_linear
+            .init::<Backend>(&device)
+            .unwrap()
+            .save_file(file_path.clone(), &recorder)
+            .unwrap();
+
+        // The buffers are rebuilt from the config instead of the record
+        let encoder_loaded = config
+            .init::<Backend>(&device)
+            .unwrap()
+            .load_file(file_path, &recorder, &device)
+            .unwrap();
+        assert_eq!(encoder_loaded.freqs.into_data(), encoder.freqs.into_data());
+        assert_eq!(
+            encoder_loaded.phases.into_data(),
+            encoder.phases.into_data()
+        );
     }
 }
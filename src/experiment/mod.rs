@@ -1,26 +1,72 @@
+pub mod progress;
 pub mod tester;
 pub mod trainer;
 
 use crate::*;
 
 use self::{tester::*, trainer::*};
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use burn::{
-    data::dataset::Dataset, nn::loss, prelude::*,
-    tensor::backend::AutodiffBackend,
+    data::dataset::Dataset, prelude::*, tensor::backend::AutodiffBackend,
 };
 use kdam::tqdm;
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Config, Debug)]
 pub struct ExperimentConfig {
     pub artifact_directory: String,
+    #[config(default = true)]
+    pub artifact_fallback_to_temp_directory: bool,
+    #[config(default = true)]
+    pub clamp_output: bool,
+    #[config(default = "CollageLayout::Stacked")]
+    pub collage_layout: CollageLayout,
+    #[config(default = 0.0)]
+    pub color_noise_std: f32,
     pub dataset: dataset::SimpleNerfDatasetConfig,
     pub dataset_file_path_or_url: String,
+    #[config(default = 0.0)]
+    pub distortion_weight: f32,
     pub epoch_count: usize,
+    // NOTE: Each training image is split into the ray chunks, and the
+    // optimizer steps once per image with the accumulated gradients
+    #[config(default = 1)]
+    pub grad_accumulation_steps: usize,
     pub learning_rate: f64,
+    #[config(default = "LossReduction::Mean")]
+    pub loss_reduction: LossReduction,
+    #[config(default = "None")]
+    pub lpips_weights_path: Option<String>,
+    #[config(default = "LrSchedule::Constant")]
+    pub lr_schedule: LrSchedule,
+    #[config(default = 0.0)]
+    pub opacity_reg_weight: f32,
+    #[config(default = "OutputFormat::Png")]
+    pub output_format: OutputFormat,
+    #[config(default = "ProfilingConfig::new()")]
+    pub profiling: ProfilingConfig,
+    #[config(default = false)]
+    pub psnr_per_channel: bool,
+    #[config(default = "crate::loss::ReconstructionLoss::Mse")]
+    pub reconstruction_loss: crate::loss::ReconstructionLoss,
     pub renderer: renderer::VolumeRendererConfig,
+    // NOTE: Each output is also saved as an RGBA PNG, where the alpha
+    // is the accumulated opacity
+    #[config(default = false)]
+    pub save_alpha: bool,
+    #[config(default = "None")]
+    pub seed: Option<u64>,
     pub train_ratio: f32,
+    #[config(default = 0.0)]
+    pub weight_decay: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExperimentConfigBuilder {
+    config: ExperimentConfig,
 }
 
 pub struct Experiment<B: AutodiffBackend> {
@@ -36,7 +82,38 @@ impl ExperimentConfig {
     ) -> Result<Experiment<B>> {
         let artifact_directory = PathBuf::from(&self.artifact_directory);
 
-        let criterion = loss::MseLoss::new();
+        // Prepare the Directory to Save Artifacts
+        if do_clear_artifacts_directory {
+            if artifact_directory.is_dir() {
+                fs::remove_dir_all(&artifact_directory)?;
+            } else if artifact_directory.exists() {
+                fs::remove_file(&artifact_directory)?;
+            }
+        } else {
+            if artifact_directory.is_dir() {
+                bail!(
+                    "Artifacts directory already exists: {:?}",
+                    artifact_directory
+                );
+            } else if artifact_directory.exists() {
+                bail!(
+                    "Artifacts directory is not a directory: {:?}",
+                    artifact_directory
+                );
+            }
+        }
+        check_artifact_directory(&artifact_directory)?;
+
+        // NOTE: Seeding the backend before any random initialization
+        if let Some(seed) = self.seed {
+            B::seed(seed);
+        }
+
+        let criterion = self.reconstruction_loss;
+
+        let criterion_distortion = crate::loss::DistortionLoss::new();
+
+        let criterion_opacity = crate::loss::OpacityLoss::new();
 
         let datasets = self
             .dataset
@@ -46,6 +123,34 @@ impl ExperimentConfig {
         let metric_fidelity_psnr =
             metric::PsnrMetric::<B::InnerBackend>::init(device);
 
+        let metric_perceptual_lpips = self
+            .lpips_weights_path
+            .as_ref()
+            .map(|path| {
+                metric::LpipsMetric::<B::InnerBackend>::init_from_weights(
+                    path, device,
+                )
+            })
+            .transpose()?;
+
+        let channel_count = datasets.train.get_channel_count();
+        if channel_count != self.renderer.scene.color_channel_count {
+            bail!(
+                "Renderer has {} color channels but the images have {}",
+                self.renderer.scene.color_channel_count,
+                channel_count
+            );
+        }
+
+        if self.grad_accumulation_steps == 0 {
+            bail!("Gradient accumulation steps should be positive");
+        }
+
+        let output_format = self.output_format.get_image_format();
+        if !output_format.writing_enabled() {
+            bail!("Output format is not supported: {:?}", output_format);
+        }
+
         let renderer = self.renderer.init(device)?;
 
         let progress_bar = {
@@ -66,48 +171,350 @@ impl ExperimentConfig {
             bar
         };
 
-        // Prepare the Directory to Save Artifacts
-        if do_clear_artifacts_directory {
-            if artifact_directory.is_dir() {
-                fs::remove_dir_all(&artifact_directory)?;
-            } else if artifact_directory.exists() {
-                fs::remove_file(&artifact_directory)?;
-            }
-        } else {
-            if artifact_directory.is_dir() {
-                bail!(
-                    "Artifacts directory already exists: {:?}",
-                    artifact_directory
-                );
-            } else if artifact_directory.exists() {
-                bail!(
-                    "Artifacts directory is not a directory: {:?}",
-                    artifact_directory
-                );
-            }
-        }
-        fs::create_dir_all(&artifact_directory)?;
-
         self.save(artifact_directory.join("experiment.json"))?;
 
         Ok(Experiment {
             tester: Tester {
                 artifact_directory: artifact_directory.clone(),
-                dataset: datasets.test,
+                clamp_output: self.clamp_output,
+                collage_layout: self.collage_layout,
+                dataset: datasets.test.into_noiseless(),
+                dataset_source: self.dataset_file_path_or_url.clone(),
                 device: device.clone(),
                 metric_fidelity_psnr: metric_fidelity_psnr.clone(),
+                metric_perceptual_lpips,
+                output_format: self.output_format,
+                psnr_per_channel: self.psnr_per_channel,
+                save_alpha: self.save_alpha,
             },
             trainer: Trainer {
                 artifact_directory,
+                artifact_fallback_to_temp_directory: self
+                    .artifact_fallback_to_temp_directory,
+                color_noise_std: self.color_noise_std,
                 criterion,
+                criterion_distortion,
+                criterion_opacity,
                 dataset: datasets.train,
                 device: device.clone(),
+                distortion_weight: self.distortion_weight,
                 epoch_count: self.epoch_count,
+                grad_accumulation_steps: self.grad_accumulation_steps,
                 learning_rate: self.learning_rate,
+                loss_reduction: self.loss_reduction,
+                lr_schedule: self.lr_schedule,
                 metric_fidelity_psnr,
+                opacity_reg_weight: self.opacity_reg_weight,
+                profiling: self.profiling.clone(),
                 progress_bar,
                 renderer,
+                seed: self.seed,
+                weight_decay: self.weight_decay,
             },
         })
     }
 }
+
+impl Default for ExperimentConfigBuilder {
+    // NOTE: The defaults train the full model on the hosted lego-tiny scene
+    fn default() -> Self {
+        Self {
+            config: ExperimentConfig::new(
+                "artifacts/experiment".into(),
+                dataset::SimpleNerfDatasetConfig::new(20, 2.0..6.0),
+                "https://raw.githubusercontent.com/AsherJingkongChen/simple-nerf-rust/main/resources/lego-tiny/data.npz".into(),
+                10000,
+                1e-3,
+                renderer::VolumeRendererConfig::new(
+                    scene::VolumetricSceneConfig::from_input_encoder(
+                        256,
+                        encoder::EncoderConfig::Positional(encoder::PositionalEncoderConfig::new(10)),
+                    ),
+                ),
+                0.8,
+            ),
+        }
+    }
+}
+
+impl ExperimentConfigBuilder {
+    pub fn artifact_directory(
+        mut self,
+        artifact_directory: impl Into<String>,
+    ) -> Self {
+        self.config.artifact_directory = artifact_directory.into();
+        self
+    }
+
+    pub fn dataset(
+        mut self,
+        dataset: dataset::SimpleNerfDatasetConfig,
+    ) -> Self {
+        self.config.dataset = dataset;
+        self
+    }
+
+    pub fn dataset_file_path_or_url(
+        mut self,
+        dataset_file_path_or_url: impl Into<String>,
+    ) -> Self {
+        self.config.dataset_file_path_or_url = dataset_file_path_or_url.into();
+        self
+    }
+
+    pub fn epoch_count(
+        mut self,
+        epoch_count: usize,
+    ) -> Self {
+        self.config.epoch_count = epoch_count;
+        self
+    }
+
+    pub fn learning_rate(
+        mut self,
+        learning_rate: f64,
+    ) -> Self {
+        self.config.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn renderer(
+        mut self,
+        renderer: renderer::VolumeRendererConfig,
+    ) -> Self {
+        self.config.renderer = renderer;
+        self
+    }
+
+    pub fn seed(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn train_ratio(
+        mut self,
+        train_ratio: f32,
+    ) -> Self {
+        self.config.train_ratio = train_ratio;
+        self
+    }
+
+    pub fn build(self) -> Result<ExperimentConfig> {
+        if !(0.0..=1.0).contains(&self.config.train_ratio) {
+            bail!("Train ratio is not in [0, 1]: {}", self.config.train_ratio);
+        }
+        if self.config.epoch_count == 0 {
+            bail!("Epoch count is zero");
+        }
+        Ok(self.config)
+    }
+}
+
+// NOTE: Creating the directory and probing it with a file
+// to detect the unwritable paths before the long training
+fn check_artifact_directory(directory: &Path) -> Result<()> {
+    let probe_path = directory.join(".write-check");
+    fs::create_dir_all(directory)
+        .and_then(|_| fs::write(&probe_path, b""))
+        .and_then(|_| fs::remove_file(&probe_path))
+        .map_err(|error| {
+            anyhow!(
+                "Artifacts directory is not writable: {:?} ({})",
+                directory,
+                error
+            )
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn get_test_config(name: &str) -> ExperimentConfig {
+        let directory =
+            std::env::temp_dir().join("simple-nerf-tests").join(name);
+        fs::create_dir_all(&directory).unwrap();
+
+        let dataset_file_path = directory.join("data.npz");
+        fs::write(
+            &dataset_file_path,
+            dataset::tests::synthesize_test_data(5, 8, 8),
+        )
+        .unwrap();
+
+        ExperimentConfig {
+            artifact_directory: directory
+                .join("artifacts")
+                .to_string_lossy()
+                .into(),
+            artifact_fallback_to_temp_directory: true,
+            clamp_output: true,
+            collage_layout: CollageLayout::Stacked,
+            color_noise_std: 0.0,
+            dataset: dataset::SimpleNerfDatasetConfig {
+                points_per_ray: 4,
+                distance_range: 2.0..6.0,
+                camera_convention: dataset::CameraConvention::OpenGL,
+                degenerate_ray_policy: dataset::DegenerateRayPolicy::Error,
+                download_retry_count: 3,
+                download_timeout_seconds: 300,
+                jitter_fraction: 1.0,
+                max_images: None,
+                max_views: None,
+                pixel_value_range: dataset::PixelValueRange::Auto,
+                sample_spacing: dataset::SampleSpacing::Uniform,
+                scene_bbox: None,
+                stratified: false,
+                use_ndc: false,
+                view_stride: 1,
+            },
+            dataset_file_path_or_url: dataset_file_path
+                .to_string_lossy()
+                .into(),
+            distortion_weight: 0.0,
+            epoch_count: 2,
+            grad_accumulation_steps: 1,
+            learning_rate: 1e-3,
+            loss_reduction: LossReduction::Mean,
+            lpips_weights_path: None,
+            lr_schedule: LrSchedule::Constant,
+            opacity_reg_weight: 0.0,
+            output_format: OutputFormat::Png,
+            profiling: ProfilingConfig {
+                epoch_interval: 25,
+                preview_interval: None,
+                psnr_downsampling_factor: 1,
+            },
+            psnr_per_channel: false,
+            reconstruction_loss: crate::loss::ReconstructionLoss::Mse,
+            renderer: renderer::VolumeRendererConfig::new(
+                scene::tests::get_test_config(2, 8),
+            ),
+            save_alpha: false,
+            seed: None,
+            train_ratio: 0.8,
+            weight_decay: 0.0,
+        }
+    }
+
+    #[test]
+    fn experiment_unwritable_artifact_directory() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            get_test_config("experiment_unwritable_artifact_directory");
+        let blocker =
+            PathBuf::from(&config.artifact_directory).with_file_name("blocker");
+        fs::write(&blocker, b"").unwrap();
+        config.artifact_directory =
+            blocker.join("artifacts").to_string_lossy().into();
+
+        let experiment = config.init::<Backend>(&device, true);
+        let error = experiment.err().unwrap().to_string();
+        assert!(
+            error.starts_with("Artifacts directory is not writable"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn experiment_config_builder() {
+        let config = ExperimentConfigBuilder::default()
+            .epoch_count(1000)
+            .build()
+            .unwrap();
+        assert_eq!(config.epoch_count, 1000);
+        assert_eq!(config.train_ratio, 0.8);
+        assert_eq!(config.renderer.scene.hidden_size, 256);
+        assert_eq!(
+            config.renderer.scene.position_encoder.get_output_size(3),
+            63
+        );
+        assert!(config
+            .dataset_file_path_or_url
+            .ends_with("lego-tiny/data.npz"));
+
+        let config = ExperimentConfigBuilder::default()
+            .artifact_directory("artifacts/builder")
+            .seed(3)
+            .train_ratio(1.0)
+            .build()
+            .unwrap();
+        assert_eq!(config.artifact_directory, "artifacts/builder");
+        assert_eq!(config.seed, Some(3));
+
+        assert!(ExperimentConfigBuilder::default()
+            .train_ratio(1.5)
+            .build()
+            .is_err());
+        assert!(ExperimentConfigBuilder::default()
+            .epoch_count(0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn experiment_seed() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        // NOTE: The soft density keeps the render from being all black
+        let mut config = get_test_config("experiment_seed");
+        config.renderer = renderer::VolumeRendererConfig::new(
+            scene::tests::get_test_config(2, 8).with_density_activation(
+                scene::Activation::Softplus {
+                    beta: 1.0,
+                    shift: 0.0,
+                },
+            ),
+        );
+        config.seed = Some(7);
+        let input = config
+            .dataset
+            .init_from_file_path::<Backend>(
+                &config.dataset_file_path_or_url,
+                &device,
+            )
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .into_input(&device);
+
+        // The renders of the seeded experiments are reproducible
+        {
+            let _lock = scene::tests::SEED_LOCK
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let get_seeded_render = || {
+                config
+                    .init::<Backend>(&device, true)
+                    .unwrap()
+                    .trainer
+                    .renderer
+                    .forward(
+                        input.directions.clone(),
+                        input.intervals.clone(),
+                        input.positions.clone(),
+                    )
+                    .into_data()
+                    .value
+            };
+            let render = get_seeded_render();
+            assert!(render.iter().any(|value| *value > 0.0));
+            assert_eq!(render, get_seeded_render());
+        }
+
+        let saved = ExperimentConfig::load(
+            PathBuf::from(&config.artifact_directory).join("experiment.json"),
+        )
+        .unwrap();
+        assert_eq!(saved.seed, Some(7));
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{bail, Result};
+use burn::prelude::*;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+use regex::Regex;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+// NOTE: The trainer saves the frames in this directory of the artifacts
+pub const PROGRESS_DIRECTORY_NAME: &str = "progress";
+
+#[derive(Config, Debug)]
+pub struct ProgressAnimationConfig {
+    #[config(default = 100)]
+    pub frame_delay_ms: u32,
+}
+
+impl ProgressAnimationConfig {
+    // NOTE: The frames are named by `get_frame_file_name`, and the unreadable
+    // or mismatched ones are skipped
+    pub fn export(
+        &self,
+        progress_directory: impl AsRef<Path>,
+        output_path: impl AsRef<Path>,
+    ) -> Result<Vec<usize>> {
+        let pattern = Regex::new(r"^epoch-(\d+)\.png$")?;
+
+        let mut frame_paths = fs::read_dir(progress_directory)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let epoch = pattern
+                    .captures(path.file_name()?.to_str()?)?
+                    .get(1)?
+                    .as_str()
+                    .parse::<usize>()
+                    .ok()?;
+                Some((epoch, path))
+            })
+            .collect::<Vec<_>>();
+        frame_paths.sort_by_key(|(epoch, _)| *epoch);
+
+        let mut epochs = vec![];
+        let mut frames: Vec<RgbaImage> = vec![];
+        for (epoch, path) in frame_paths {
+            let frame = match image::open(&path) {
+                Ok(frame) => frame.into_rgba8(),
+                Err(_) => continue,
+            };
+            if frames
+                .first()
+                .is_some_and(|first| first.dimensions() != frame.dimensions())
+            {
+                continue;
+            }
+            epochs.push(epoch);
+            frames.push(frame);
+        }
+        if frames.is_empty() {
+            bail!("No preview frames are found");
+        }
+
+        let mut encoder =
+            GifEncoder::new(BufWriter::new(File::create(output_path)?));
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.into_iter().map(|frame| {
+            Frame::from_parts(
+                frame,
+                0,
+                0,
+                Delay::from_numer_denom_ms(self.frame_delay_ms, 1),
+            )
+        }))?;
+
+        Ok(epochs)
+    }
+}
+
+pub fn get_frame_file_name(epoch: usize) -> String {
+    format!("epoch-{:05}.png", epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::gif::GifDecoder, AnimationDecoder, RgbImage};
+    use std::io::BufReader;
+
+    #[test]
+    fn progress_animation_export() {
+        let directory = std::env::temp_dir()
+            .join("simple-nerf-tests")
+            .join("progress_animation_export");
+        let progress_directory = directory.join("progress");
+        if progress_directory.exists() {
+            fs::remove_dir_all(&progress_directory).unwrap();
+        }
+        fs::create_dir_all(&progress_directory).unwrap();
+
+        for epoch in [100, 2, 10] {
+            RgbImage::from_pixel(4, 4, image::Rgb([epoch as u8, 0, 0]))
+                .save(progress_directory.join(format!("epoch-{}.png", epoch)))
+                .unwrap();
+        }
+        RgbImage::new(2, 2)
+            .save(progress_directory.join("epoch-50.png"))
+            .unwrap();
+        fs::write(progress_directory.join("epoch-5.png"), b"corrupted")
+            .unwrap();
+        fs::write(progress_directory.join("notes.txt"), b"unrelated").unwrap();
+
+        let output_path = directory.join("progress.gif");
+        let epochs = ProgressAnimationConfig::new()
+            .export(&progress_directory, &output_path)
+            .unwrap();
+        assert_eq!(epochs, [2, 10, 100]);
+
+        let frames =
+            GifDecoder::new(BufReader::new(File::open(&output_path).unwrap()))
+                .unwrap()
+                .into_frames()
+                .collect_frames()
+                .unwrap();
+        assert_eq!(frames.len(), 3);
+        let reds = frames
+            .iter()
+            .map(|frame| frame.buffer().get_pixel(0, 0)[0])
+            .collect::<Vec<_>>();
+        assert_eq!(reds, [2, 10, 100]);
+
+        let empty_directory = directory.join("empty");
+        fs::create_dir_all(&empty_directory).unwrap();
+        assert!(ProgressAnimationConfig::new()
+            .export(&empty_directory, directory.join("empty.gif"))
+            .is_err());
+    }
+}
@@ -1,39 +1,150 @@
 use crate::*;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use burn::{
-    data::dataset::Dataset, prelude::*, tensor::backend::AutodiffBackend,
+    data::dataset::Dataset,
+    prelude::*,
+    tensor::{backend::AutodiffBackend, Distribution},
+};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops, ImageFormat, RgbImage, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::BufWriter,
+    ops::Range,
+    path::{Path, PathBuf},
+    time,
 };
-use image::{ImageFormat, RgbImage};
-use std::{path::PathBuf, time};
 
 #[derive(Clone, Debug)]
 pub struct Tester<B: AutodiffBackend> {
     pub(super) artifact_directory: PathBuf,
+    pub(super) clamp_output: bool,
+    pub(super) collage_layout: CollageLayout,
     pub(super) dataset: dataset::SimpleNerfDataset<B>,
+    pub(super) dataset_source: String,
     pub(super) device: B::Device,
     pub(super) metric_fidelity_psnr: metric::PsnrMetric<B::InnerBackend>,
+    pub(super) metric_perceptual_lpips:
+        Option<metric::LpipsMetric<B::InnerBackend>>,
+    pub(super) output_format: OutputFormat,
+    pub(super) psnr_per_channel: bool,
+    pub(super) save_alpha: bool,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum CollageLayout {
+    // NOTE: Each row is a pair of the target and the output
+    Stacked,
+    // NOTE: Each cell is a pair of the target and the output,
+    // and the final row is padded with black cells
+    Grid {
+        columns: usize,
+    },
+    // NOTE: The row of the targets is over the row of the outputs
+    SideBySide,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    // NOTE: The quality is in `[1, 100]`
+    Jpeg {
+        quality: u8,
+    },
+    Png,
+    // NOTE: The WebP images are lossless
+    WebP,
 }
 
 #[derive(Config, Debug)]
 pub struct TestOutput {
-    pub collage_path: PathBuf,
+    pub collage_path: Option<PathBuf>,
     pub eval_output: EvaluationOutput,
 }
 
-#[derive(Config, Debug)]
+// NOTE: It is displayed as the summary, so `Config` is implemented
+// without the derive, which would display the JSON
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct EvaluationOutput {
+    // NOTE: The first-frame latency is excluded, since the renderer is
+    // warmed up before the timed frames
     pub fps: f64,
     pub items: Vec<EvaluationOutputItem>,
+    pub median_render_ms: f64,
+}
+
+// NOTE: The metrics are the mean, the minimum and the maximum over the items
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EvaluationSummary {
+    pub fidelity_psnr: [f64; 3],
+    pub fps: f64,
+    // NOTE: It is only summarized if every item has the LPIPS
+    pub lpips: Option<[f64; 3]>,
+}
+
+// NOTE: The file names are relative to the artifact directory
+#[derive(Config, Debug)]
+pub struct TestManifest {
+    pub dataset_source: String,
+    pub output_file_names: Vec<String>,
+    // NOTE: The record is absent if the renderer is not saved
+    // in the artifact directory
+    pub renderer_record_file_name: Option<String>,
+    // NOTE: It is the FNV-1a hash of the record file in hexadecimal,
+    // which is stable across the builds
+    pub renderer_record_hash: Option<String>,
+    // NOTE: It is absent for the empty test set
+    pub summary: Option<EvaluationSummary>,
 }
 
 #[derive(Config, Debug)]
 pub struct EvaluationOutputItem {
     pub index: usize,
     pub fidelity_psnr: f64,
+    // NOTE: It is only evaluated if `psnr_per_channel` is enabled
+    pub fidelity_psnr_per_channel: Option<Vec<f64>>,
+    // NOTE: It is only evaluated with the pretrained weights
+    pub lpips: Option<f64>,
+    pub render_ms: f64,
 }
 
 impl<B: AutodiffBackend> Tester<B> {
+    // NOTE: The options are restored from the saved `experiment.json`,
+    // and the test dataset is given since it is not saved. The dataset is
+    // made noiseless so the evaluations are reproducible
+    pub fn from_artifacts(
+        artifact_directory: impl Into<PathBuf>,
+        dataset: dataset::SimpleNerfDataset<B>,
+        device: &B::Device,
+    ) -> Result<Self> {
+        let artifact_directory = artifact_directory.into();
+        let config = super::ExperimentConfig::load(
+            artifact_directory.join("experiment.json"),
+        )?;
+
+        Ok(Self {
+            artifact_directory,
+            clamp_output: config.clamp_output,
+            collage_layout: config.collage_layout,
+            dataset: dataset.into_noiseless(),
+            dataset_source: config.dataset_file_path_or_url,
+            device: device.clone(),
+            metric_fidelity_psnr: metric::PsnrMetric::init(device),
+            metric_perceptual_lpips: config
+                .lpips_weights_path
+                .map(|path| {
+                    metric::LpipsMetric::init_from_weights(path, device)
+                })
+                .transpose()?,
+            output_format: config.output_format,
+            psnr_per_channel: config.psnr_per_channel,
+            save_alpha: config.save_alpha,
+        })
+    }
+
     pub fn test(
         &self,
         renderer: renderer::VolumeRenderer<B::InnerBackend>,
@@ -41,12 +152,72 @@ impl<B: AutodiffBackend> Tester<B> {
     where
         B::FloatElem: Into<f64>,
     {
+        self.test_supersampled(renderer, 1)
+    }
+
+    /// Each pixel is rendered from `samples_per_pixel` jittered rays
+    /// within its footprint and the outputs are averaged
+    pub fn test_supersampled(
+        &self,
+        renderer: renderer::VolumeRenderer<B::InnerBackend>,
+        samples_per_pixel: usize,
+    ) -> Result<TestOutput>
+    where
+        B::FloatElem: Into<f64>,
+    {
+        if samples_per_pixel == 0 {
+            bail!("Samples per pixel should be positive");
+        }
+
+        assert!(
+            !self.dataset.has_noisy_distance(),
+            "Test dataset must have the noiseless distances"
+        );
+
         let count = self.dataset.len();
         eprintln!("Testing on {} items", count);
 
+        // NOTE: The empty test set has no collage and no rendering rate
+        if count == 0 {
+            eprintln!("Warning: Test set is empty, skipping the collage");
+            let eval_output = EvaluationOutput {
+                fps: 0.0,
+                items: vec![],
+                median_render_ms: 0.0,
+            };
+            eval_output
+                .save(self.artifact_directory.join("evaluation-output.json"))?;
+            self.save_manifest(None, vec!["evaluation-output.json".into()])?;
+
+            return Ok(TestOutput {
+                collage_path: None,
+                eval_output,
+            });
+        }
+
+        // NOTE: The first frame would include the pipeline compilation,
+        // so the renderer is warmed up and it is excluded from the FPS
+        if let Some(data) = self.dataset.get(0) {
+            let [height, width, points_per_ray, ..] =
+                data.directions.shape.dims;
+            renderer.warmup([height, width, points_per_ray], &self.device);
+
+            if let Some(memory_budget_bytes) = renderer.memory_budget_bytes() {
+                eprintln!(
+                    "Rendering in {} chunks within {} bytes",
+                    renderer
+                        .render_stats([height, width], points_per_ray)
+                        .chunk_count,
+                    memory_budget_bytes
+                );
+            }
+        }
+
+        // NOTE: Each item is written into the collage buffer and dropped,
+        // so only one rendered image is held on the device at a time
+        let mut collage = None;
         let mut eval_output_items = vec![];
-        let mut input_images = vec![];
-        let mut output_images = vec![];
+        let mut output_file_names = vec![];
         let mut time_secs_rendering = 0.0;
 
         // Testing and Evaluating
@@ -54,69 +225,981 @@ impl<B: AutodiffBackend> Tester<B> {
             let timer_from_input_to_output = time::Instant::now();
 
             let input = data.into_input(&self.device);
-            let output_image = renderer.forward(
+            let (output_image, output_opacity) = get_supersampled_image(
+                &renderer,
                 input.directions,
+                input.distances,
                 input.intervals,
                 input.positions,
+                samples_per_pixel,
             );
 
-            time_secs_rendering +=
+            let time_secs_rendering_item =
                 timer_from_input_to_output.elapsed().as_secs_f64();
+            time_secs_rendering += time_secs_rendering_item;
 
             let fidelity_psnr = self
                 .metric_fidelity_psnr
                 .forward(output_image.clone(), input.image.clone())
                 .into_scalar()
                 .into();
+            let input_image = &input.image;
+            let fidelity_psnr_per_channel = self.psnr_per_channel.then(|| {
+                self.metric_fidelity_psnr
+                    .forward_per_channel(
+                        output_image.clone(),
+                        input_image.clone(),
+                    )
+                    .into_data()
+                    .value
+                    .into_iter()
+                    .map(Into::into)
+                    .collect()
+            });
+            let lpips = self.metric_perceptual_lpips.as_ref().map(|metric| {
+                metric
+                    .forward(output_image.clone(), input_image.clone())
+                    .into_scalar()
+                    .into()
+            });
 
             eval_output_items.push(EvaluationOutputItem {
                 index,
                 fidelity_psnr,
+                fidelity_psnr_per_channel,
+                lpips,
+                render_ms: time_secs_rendering_item * 1e3,
             });
-            input_images.push(input.image);
-            output_images.push(output_image);
+            let clipped_pixel_count = get_clipped_pixel_count(&output_image);
+            let [height, width, ..] = output_image.dims();
+            let output_image =
+                get_displayed_image(output_image, self.clamp_output);
+            if self.save_alpha {
+                let output_file_name = format!("output-{:03}.png", index);
+                get_rgba_image(output_image.clone(), output_opacity)?
+                    .save(self.artifact_directory.join(&output_file_name))?;
+                output_file_names.push(output_file_name);
+            }
+            self.collage_layout.write(
+                collage.get_or_insert_with(|| {
+                    let [width, height] =
+                        self.collage_layout.get_size(count, height, width);
+                    RgbImage::new(width as u32, height as u32)
+                }),
+                index,
+                &get_rgb_image(input.image)?,
+                &get_rgb_image(output_image)?,
+            );
 
-            eprintln!("Item {:03} ┃ PSNR = {:.2} dB", index, fidelity_psnr);
+            eprintln!(
+                "Item {:03} ┃ PSNR = {:.2} dB ┃ {:.1} ms ┃ {} clipped pixels",
+                index,
+                fidelity_psnr,
+                time_secs_rendering_item * 1e3,
+                clipped_pixel_count
+            );
         }
 
         // Saving the Outputs
-        let fps_rendering = count as f64 / time_secs_rendering;
+        // NOTE: The median is robust to the slow warm-up views
+        let median_render_ms = get_median(
+            eval_output_items.iter().map(|item| item.render_ms).collect(),
+        );
+        let fps_rendering = 1e3 / median_render_ms;
         eprintln!(
-            "Rendering time ┃ {:.3} sec ┃ {:.2} FPS",
-            time_secs_rendering, fps_rendering
+            "Rendering time ┃ {:.3} sec ┃ {:.2} FPS ┃ Median {:.1} ms",
+            time_secs_rendering,
+            count as f64 / time_secs_rendering,
+            median_render_ms
         );
 
         let eval_output = EvaluationOutput {
             items: eval_output_items,
             fps: fps_rendering,
+            median_render_ms,
         };
         eval_output
-            .save(&self.artifact_directory.join("evaluation-output.json"))?;
+            .save(self.artifact_directory.join("evaluation-output.json"))?;
+        eprintln!("{}", eval_output);
+
+        // NOTE: The output images are already in the same color space as
+        // the targets, since the renderer applies its output transform
+        let collage_path = self
+            .artifact_directory
+            .join(format!("collage.{}", self.output_format.get_extension()));
+        let collage = collage.ok_or(anyhow!("Collage is empty"))?;
+        self.output_format.save(&collage, &collage_path)?;
+        eprintln!("Collage is saved at {:?}", collage_path);
 
-        let collage_path = self.artifact_directory.join("collage.png");
-        let collage = {
-            let image = Tensor::cat(
+        output_file_names.splice(
+            0..0,
+            [
+                collage_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into())
+                    .unwrap_or_default(),
+                "evaluation-output.json".into(),
+            ],
+        );
+        self.save_manifest(Some(eval_output.summary()), output_file_names)?;
+
+        Ok(TestOutput {
+            collage_path: Some(collage_path),
+            eval_output,
+        })
+    }
+}
+
+impl<B: AutodiffBackend> Tester<B> {
+    // NOTE: The manifest links the outputs to the saved renderer and
+    // the dataset, so the artifact directory is self-describing
+    fn save_manifest(
+        &self,
+        summary: Option<EvaluationSummary>,
+        output_file_names: Vec<String>,
+    ) -> Result<()> {
+        let renderer_record_file_name = "volume-renderer.mpk";
+        let renderer_record_hash =
+            fs::read(self.artifact_directory.join(renderer_record_file_name))
+                .ok()
+                .map(|bytes| format!("{:016x}", get_fnv1a_hash(&bytes)));
+
+        TestManifest {
+            dataset_source: self.dataset_source.clone(),
+            output_file_names,
+            renderer_record_file_name: renderer_record_hash
+                .as_ref()
+                .map(|_| renderer_record_file_name.into()),
+            renderer_record_hash,
+            summary,
+        }
+        .save(self.artifact_directory.join("test-manifest.json"))?;
+
+        Ok(())
+    }
+}
+
+impl Config for EvaluationOutput {}
+
+impl EvaluationOutput {
+    pub fn summary(&self) -> EvaluationSummary {
+        let get_stats = |values: Vec<f64>| {
+            let count = values.len() as f64;
+            values.into_iter().fold(
+                [0.0, f64::INFINITY, f64::NEG_INFINITY],
+                |[mean, min, max], value| {
+                    [mean + value / count, min.min(value), max.max(value)]
+                },
+            )
+        };
+
+        EvaluationSummary {
+            fidelity_psnr: get_stats(
+                self.items.iter().map(|item| item.fidelity_psnr).collect(),
+            ),
+            fps: self.fps,
+            lpips: self
+                .items
+                .iter()
+                .map(|item| item.lpips)
+                .collect::<Option<Vec<_>>>()
+                .filter(|values| !values.is_empty())
+                .map(get_stats),
+        }
+    }
+}
+
+impl fmt::Display for EvaluationOutput {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
+impl fmt::Display for EvaluationSummary {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        let [mean, min, max] = self.fidelity_psnr;
+        write!(
+            f,
+            "PSNR ┃ Mean {:.2} dB ┃ Min {:.2} dB ┃ Max {:.2} dB",
+            mean, min, max
+        )?;
+        if let Some([mean, min, max]) = self.lpips {
+            write!(
+                f,
+                "\nLPIPS ┃ Mean {:.4} ┃ Min {:.4} ┃ Max {:.4}",
+                mean, min, max
+            )?;
+        }
+        write!(f, "\nRendering ┃ {:.2} FPS", self.fps)
+    }
+}
+
+impl CollageLayout {
+    // NOTE: The size is `[width, height]` in pixels for `count` pairs
+    // of the images in `height x width`
+    pub fn get_size(
+        &self,
+        count: usize,
+        height: usize,
+        width: usize,
+    ) -> [usize; 2] {
+        match *self {
+            CollageLayout::Stacked => [width * 2, height * count],
+            CollageLayout::Grid {
+                columns,
+            } => {
+                let columns = columns.max(1);
+                [width * 2 * columns, height * count.div_ceil(columns)]
+            },
+            CollageLayout::SideBySide => [width * count, height * 2],
+        }
+    }
+
+    // NOTE: The input and the output at `index` are placed in their cells
+    // of the layout, and the padded cells are left untouched
+    pub fn write(
+        &self,
+        collage: &mut RgbImage,
+        index: usize,
+        input_image: &RgbImage,
+        output_image: &RgbImage,
+    ) {
+        #[cfg(test)]
+        tests::COLLAGE_SCENE_POINT_COUNTS.with(|counts| {
+            counts.borrow_mut().push(
+                renderer::tests::SCENE_POINT_COUNT.with(|count| count.get()),
+            )
+        });
+
+        let (width, height) = (input_image.width(), input_image.height());
+        let index = index as u32;
+        let (input_origin, output_origin) = match *self {
+            CollageLayout::Stacked => {
+                ((0, height * index), (width, height * index))
+            },
+            CollageLayout::Grid {
+                columns,
+            } => {
+                let columns = columns.max(1) as u32;
+                let left = width * 2 * (index % columns);
+                let top = height * (index / columns);
+                ((left, top), (left + width, top))
+            },
+            CollageLayout::SideBySide => {
+                ((width * index, 0), (width * index, height))
+            },
+        };
+        for (image, (left, top)) in
+            [(input_image, input_origin), (output_image, output_origin)]
+        {
+            imageops::replace(collage, image, left as i64, top as i64);
+        }
+    }
+
+    // NOTE: The collage is arranged from all the images at once, so it is
+    // only the reference for the pairs written one at a time
+    #[cfg(test)]
+    fn arrange<B: Backend>(
+        &self,
+        input_images: Vec<Tensor<B, 3>>,
+        output_images: Vec<Tensor<B, 3>>,
+    ) -> Tensor<B, 3> {
+        match *self {
+            CollageLayout::Stacked => Tensor::cat(
                 vec![
                     Tensor::cat(input_images, 0),
                     Tensor::cat(output_images, 0),
                 ],
                 1,
-            );
-            let [height, width, ..] = image.dims();
-            let image = (image.clamp(0.0, 1.0) * 255.0)
-                .into_data()
-                .convert::<u8>()
-                .value;
-
-            RgbImage::from_vec(width as u32, height as u32, image)
-                .ok_or(anyhow!("Collage buffer is too small"))?
+            ),
+            CollageLayout::Grid {
+                columns,
+            } => {
+                let columns = columns.max(1);
+                let cell_blank = input_images[0].zeros_like();
+                let mut cells = input_images
+                    .into_iter()
+                    .zip(output_images)
+                    .map(|(input_image, output_image)| {
+                        Tensor::cat(vec![input_image, output_image], 1)
+                    })
+                    .collect::<Vec<_>>();
+                while cells.len() % columns != 0 {
+                    cells.push(Tensor::cat(
+                        vec![cell_blank.clone(), cell_blank.clone()],
+                        1,
+                    ));
+                }
+                let rows = cells
+                    .chunks(columns)
+                    .map(|cells| Tensor::cat(cells.to_vec(), 1))
+                    .collect();
+                Tensor::cat(rows, 0)
+            },
+            CollageLayout::SideBySide => Tensor::cat(
+                vec![
+                    Tensor::cat(input_images, 1),
+                    Tensor::cat(output_images, 1),
+                ],
+                0,
+            ),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn get_extension(&self) -> &'static str {
+        self.get_image_format().extensions_str()[0]
+    }
+
+    pub fn get_image_format(&self) -> ImageFormat {
+        match *self {
+            OutputFormat::Jpeg {
+                ..
+            } => ImageFormat::Jpeg,
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::WebP => ImageFormat::WebP,
+        }
+    }
+
+    pub fn save(
+        &self,
+        image: &RgbImage,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let format = self.get_image_format();
+        if !format.writing_enabled() {
+            bail!("Output format is not supported: {:?}", format);
+        }
+
+        match *self {
+            OutputFormat::Jpeg {
+                quality,
+            } => image.write_with_encoder(JpegEncoder::new_with_quality(
+                BufWriter::new(File::create(path)?),
+                quality.clamp(1, 100),
+            ))?,
+            _ => image.save_with_format(path, format)?,
+        }
+        Ok(())
+    }
+}
+
+// NOTE: The image is clamped to `[0, 1]`,
+// and the grayscale image is broadcast to RGB
+pub(super) fn get_rgb_image<B: Backend>(
+    image: Tensor<B, 3>
+) -> Result<RgbImage> {
+    let [height, width, channel_count] = image.dims();
+    let image = if channel_count == 1 {
+        image.repeat(2, 3)
+    } else {
+        image
+    };
+    let image =
+        (image.clamp(0.0, 1.0) * 255.0).into_data().convert::<u8>().value;
+
+    RgbImage::from_vec(width as u32, height as u32, image)
+        .ok_or(anyhow!("Image buffer is too small"))
+}
+
+// NOTE: The image is composited over black, so the colors are divided by
+// the opacity to be stored unassociated with the alpha
+fn get_rgba_image<B: Backend>(
+    image: Tensor<B, 3>,
+    opacity: Tensor<B, 3>,
+) -> Result<RgbaImage> {
+    let [height, width, channel_count] = image.dims();
+    let image = if channel_count == 1 {
+        image.repeat(2, 3)
+    } else {
+        image
+    };
+    let opacity = opacity.clamp(0.0, 1.0);
+    let image =
+        Tensor::cat(vec![image / opacity.clone().clamp_min(1e-10), opacity], 2);
+    let image =
+        (image.clamp(0.0, 1.0) * 255.0).into_data().convert::<u8>().value;
+
+    RgbaImage::from_vec(width as u32, height as u32, image)
+        .ok_or(anyhow!("Image buffer is too small"))
+}
+
+// NOTE: The directions are affine in the pixel coordinates, so the
+// pixel footprint is recovered from the differences of neighbouring rays
+fn get_supersampled_image<B: Backend>(
+    renderer: &renderer::VolumeRenderer<B>,
+    directions: Tensor<B, 4>,
+    distances: Tensor<B, 4>,
+    intervals: Tensor<B, 4>,
+    positions: Tensor<B, 4>,
+    samples_per_pixel: usize,
+) -> (Tensor<B, 3>, Tensor<B, 3>) {
+    if samples_per_pixel <= 1 {
+        let outputs =
+            renderer.forward_with_aux(directions, intervals, positions);
+        return (outputs.image, outputs.accumulated_opacity);
+    }
+
+    let [height, width, ..] = directions.dims();
+    let get_step = |size: usize, ranges: [Range<usize>; 2]| {
+        if size < 2 {
+            return directions.zeros_like().slice([0..1, 0..1, 0..1, 0..3]);
+        }
+        let [range_y, range_x] = ranges;
+        directions.clone().slice([range_y, range_x, 0..1, 0..3])
+            - directions.clone().slice([0..1, 0..1, 0..1, 0..3])
+    };
+    let step_x = get_step(width, [0..1, 1..2]);
+    let step_y = get_step(height, [1..2, 0..1]);
+
+    let mut sums = None;
+    for _ in 0..samples_per_pixel {
+        let jitters = Tensor::<B, 4>::random(
+            [height, width, 1, 2],
+            Distribution::Uniform(-0.5, 0.5),
+            &directions.device(),
+        );
+        let offsets = step_x.clone()
+            * jitters.clone().slice([0..height, 0..width, 0..1, 0..1])
+            + step_y.clone() * jitters.slice([0..height, 0..width, 0..1, 1..2]);
+        let outputs = renderer.forward_with_aux(
+            directions.clone() + offsets.clone(),
+            intervals.clone(),
+            positions.clone() + offsets * distances.clone(),
+        );
+        let (image, opacity) = (outputs.image, outputs.accumulated_opacity);
+        sums = Some(match sums {
+            Some((image_sum, opacity_sum)) => {
+                (image_sum + image, opacity_sum + opacity)
+            },
+            None => (image, opacity),
+        });
+    }
+
+    let (image_sum, opacity_sum) = sums.unwrap();
+    (
+        image_sum / samples_per_pixel as f32,
+        opacity_sum / samples_per_pixel as f32,
+    )
+}
+
+// NOTE: The pixels having any channel outside `[0, 1]`
+fn get_clipped_pixel_count<B: Backend>(image: &Tensor<B, 3>) -> usize {
+    let is_clipped = image.clone().lower_elem(0.0).int()
+        + image.clone().greater_elem(1.0).int();
+    is_clipped.max_dim(2).sum().into_scalar().elem::<i64>() as usize
+}
+
+// NOTE: Without clamping, the image is scaled down by its maximum,
+// so the saturated regions stay visible instead of being clipped
+fn get_displayed_image<B: Backend>(
+    image: Tensor<B, 3>,
+    clamp_output: bool,
+) -> Tensor<B, 3> {
+    if clamp_output {
+        return image;
+    }
+    let max = image.clone().max().into_scalar().elem::<f32>();
+    if max > 1.0 {
+        image / max
+    } else {
+        image
+    }
+}
+
+fn get_fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn get_median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::module::AutodiffModule;
+    use std::cell::RefCell;
+
+    type Backend = burn::backend::NdArray;
+
+    thread_local! {
+        // NOTE: The scene points evaluated before each pair is written
+        pub(super) static COLLAGE_SCENE_POINT_COUNTS: RefCell<Vec<usize>> =
+            const { RefCell::new(vec![]) };
+    }
+
+    #[test]
+    fn collage_layout_dimensions() {
+        let device = Default::default();
+
+        let get_images = |value| {
+            (0..5)
+                .map(|_| Tensor::<Backend, 3>::full([4, 6, 3], value, &device))
+                .collect::<Vec<_>>()
+        };
+        let get_collage = |layout: CollageLayout| {
+            layout.arrange(get_images(0.25), get_images(0.75))
         };
-        collage.save_with_format(&collage_path, ImageFormat::Png)?;
-        eprintln!("Collage is saved at {:?}", collage_path);
 
-        Ok(TestOutput {
-            collage_path,
-            eval_output,
-        })
+        assert_eq!(get_collage(CollageLayout::Stacked).dims(), [20, 12, 3]);
+        assert_eq!(get_collage(CollageLayout::SideBySide).dims(), [8, 30, 3]);
+
+        let collage = get_collage(CollageLayout::Grid {
+            columns: 2,
+        });
+        assert_eq!(collage.dims(), [12, 24, 3]);
+        let get_pixel = |row: usize, column: usize| {
+            collage
+                .clone()
+                .slice([row..row + 1, column..column + 1, 0..1])
+                .into_scalar()
+        };
+        assert_eq!(get_pixel(0, 0), 0.25);
+        assert_eq!(get_pixel(0, 6), 0.75);
+        assert_eq!(get_pixel(0, 12), 0.25);
+        assert_eq!(get_pixel(8, 6), 0.75);
+        assert_eq!(get_pixel(8, 12), 0.0);
+    }
+
+    #[test]
+    fn collage_layout_writing() {
+        let device = Default::default();
+
+        let get_images = |offset| {
+            (0..5)
+                .map(|index| {
+                    Tensor::<Backend, 3>::random(
+                        [4, 6, 3],
+                        burn::tensor::Distribution::Default,
+                        &device,
+                    ) * 0.5
+                        + (index + offset) as f32 * 0.05
+                })
+                .collect::<Vec<_>>()
+        };
+        let input_images = get_images(0);
+        let output_images = get_images(5);
+
+        // The pairs written one at a time match the arranged collage
+        for layout in [
+            CollageLayout::Stacked,
+            CollageLayout::Grid {
+                columns: 2,
+            },
+            CollageLayout::SideBySide,
+        ] {
+            let collage_true = get_rgb_image(
+                layout.arrange(input_images.clone(), output_images.clone()),
+            )
+            .unwrap();
+
+            let [width, height] = layout.get_size(5, 4, 6);
+            let mut collage = RgbImage::new(width as u32, height as u32);
+            for (index, (input_image, output_image)) in
+                input_images.iter().zip(&output_images).enumerate()
+            {
+                layout.write(
+                    &mut collage,
+                    index,
+                    &get_rgb_image(input_image.clone()).unwrap(),
+                    &get_rgb_image(output_image.clone()).unwrap(),
+                );
+            }
+            assert_eq!(collage, collage_true, "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn tester_collage_bounded_memory() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let config =
+            experiment::tests::get_test_config("tester_collage_bounded_memory");
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let tester = experiment.tester;
+        let [height, width, points_per_ray, ..] =
+            tester.dataset.get(0).unwrap().directions.shape.dims;
+        let image_point_count = height * width * points_per_ray;
+
+        renderer::tests::SCENE_POINT_COUNT.with(|count| count.set(0));
+        COLLAGE_SCENE_POINT_COUNTS.with(|counts| counts.borrow_mut().clear());
+        tester.test(experiment.trainer.renderer.valid()).unwrap();
+
+        // Each item is written into the collage right after it is rendered,
+        // so no more than one rendered image is alive at a time. The first
+        // image is rendered after the warm-up
+        let counts = COLLAGE_SCENE_POINT_COUNTS.take();
+        assert_eq!(counts.len(), tester.dataset.len());
+        for (index, count) in counts.into_iter().enumerate() {
+            assert_eq!(count, image_point_count * (index + 2));
+        }
+    }
+
+    #[test]
+    fn tester_empty_dataset() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            experiment::tests::get_test_config("tester_empty_dataset");
+        config.train_ratio = 1.0;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        assert_eq!(experiment.tester.dataset.len(), 0);
+
+        let output = experiment
+            .tester
+            .test(experiment.trainer.renderer.valid())
+            .unwrap();
+        assert!(output.collage_path.is_none());
+        assert!(output.eval_output.items.is_empty());
+        assert_eq!(output.eval_output.fps, 0.0);
+        assert!(experiment
+            .tester
+            .artifact_directory
+            .join("evaluation-output.json")
+            .is_file());
+    }
+
+    #[test]
+    fn tester_from_artifacts() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            experiment::tests::get_test_config("tester_from_artifacts");
+        config.collage_layout = CollageLayout::Grid {
+            columns: 3,
+        };
+        config.epoch_count = 1;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        experiment.trainer.train().unwrap();
+
+        let tester = Tester::<Backend>::from_artifacts(
+            &config.artifact_directory,
+            experiment.tester.dataset.clone(),
+            &device,
+        )
+        .unwrap();
+        assert_eq!(tester.collage_layout, config.collage_layout);
+        assert_eq!(tester.clamp_output, config.clamp_output);
+
+        let renderer = config
+            .renderer
+            .init_from_file_path::<burn::backend::NdArray>(
+                tester.artifact_directory.join("volume-renderer"),
+                &device,
+            )
+            .unwrap();
+        let output = tester.test(renderer).unwrap();
+        assert!(output.collage_path.is_some_and(|path| path.is_file()));
+    }
+
+    #[test]
+    fn tester_output_format() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            experiment::tests::get_test_config("tester_output_format");
+        config.epoch_count = 1;
+        config.output_format = OutputFormat::Jpeg {
+            quality: 80,
+        };
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+
+        let output = experiment.tester.test(renderer).unwrap();
+        let collage_path = output.collage_path.unwrap();
+        assert_eq!(collage_path.extension().unwrap(), "jpg");
+
+        // The collage is stacked with a target and an output for each item
+        let collage = image::open(&collage_path).unwrap();
+        assert_eq!(collage.width(), 16);
+        assert_eq!(collage.height(), 8);
+    }
+
+    #[test]
+    fn tester_deterministic_evaluation() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config = experiment::tests::get_test_config(
+            "tester_deterministic_evaluation",
+        );
+        config.epoch_count = 1;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+
+        // The training split with the jittered distances is made noiseless
+        let dataset = experiment.trainer.dataset.clone();
+        assert!(dataset.has_noisy_distance());
+        let tester = Tester::<Backend>::from_artifacts(
+            &config.artifact_directory,
+            dataset,
+            &device,
+        )
+        .unwrap();
+        assert!(!tester.dataset.has_noisy_distance());
+
+        let get_psnrs = || {
+            tester
+                .test(renderer.clone())
+                .unwrap()
+                .eval_output
+                .items
+                .into_iter()
+                .map(|item| item.fidelity_psnr.to_bits())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(get_psnrs(), get_psnrs());
+    }
+
+    #[test]
+    fn tester_psnr_per_channel() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            experiment::tests::get_test_config("tester_psnr_per_channel");
+        config.epoch_count = 1;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+        let output = experiment.tester.test(renderer.clone()).unwrap();
+        assert!(output.eval_output.items[0]
+            .fidelity_psnr_per_channel
+            .is_none());
+
+        config.psnr_per_channel = true;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let output = experiment.tester.test(renderer).unwrap();
+        let psnrs = output.eval_output.items[0]
+            .fidelity_psnr_per_channel
+            .clone()
+            .unwrap();
+        assert_eq!(psnrs.len(), 3);
+        assert!(psnrs.iter().all(|psnr| psnr.is_finite()));
+    }
+
+    #[test]
+    fn tester_supersampling() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config =
+            experiment::tests::get_test_config("tester_supersampling");
+        config.epoch_count = 1;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+
+        let get_psnrs = |output: TestOutput| {
+            output
+                .eval_output
+                .items
+                .iter()
+                .map(|item| item.fidelity_psnr)
+                .collect::<Vec<_>>()
+        };
+        let psnrs_single = get_psnrs(
+            experiment.tester.test_supersampled(renderer.clone(), 1).unwrap(),
+        );
+
+        // The single sample per pixel is the plain render
+        let metric = metric::PsnrMetric::init(&device);
+        let psnrs = experiment
+            .tester
+            .dataset
+            .clone()
+            .into_noiseless()
+            .iter()
+            .map(|data| {
+                let input = data.into_input(&device);
+                let output = renderer.forward(
+                    input.directions,
+                    input.intervals,
+                    input.positions,
+                );
+                metric.forward(output, input.image).into_scalar() as f64
+            })
+            .collect::<Vec<_>>();
+        assert!(!psnrs.is_empty());
+        assert_eq!(psnrs_single.len(), psnrs.len());
+        for (psnr_single, psnr) in psnrs_single.iter().zip(&psnrs) {
+            assert!((psnr_single - psnr).abs() < 1e-4, "{}", psnr_single);
+        }
+
+        let psnrs_multiple = get_psnrs(
+            experiment.tester.test_supersampled(renderer.clone(), 4).unwrap(),
+        );
+        assert_eq!(psnrs_multiple.len(), psnrs.len());
+        assert!(psnrs_multiple.iter().all(|psnr| psnr.is_finite()));
+
+        assert!(experiment.tester.test_supersampled(renderer, 0).is_err());
+    }
+
+    #[test]
+    fn evaluation_output_summary() {
+        let get_item = |index, fidelity_psnr, lpips| EvaluationOutputItem {
+            index,
+            fidelity_psnr,
+            fidelity_psnr_per_channel: None,
+            lpips,
+            render_ms: 10.0,
+        };
+        let eval_output = EvaluationOutput {
+            fps: 100.0,
+            items: vec![get_item(0, 20.0, None), get_item(1, 30.0, Some(0.1))],
+            median_render_ms: 10.0,
+        };
+
+        let summary = eval_output.summary();
+        assert_eq!(summary.fidelity_psnr, [25.0, 20.0, 30.0]);
+        assert_eq!(summary.fps, 100.0);
+        assert_eq!(summary.lpips, None);
+        assert_eq!(
+            summary.to_string(),
+            "PSNR ┃ Mean 25.00 dB ┃ Min 20.00 dB ┃ Max 30.00 dB\n\
+            Rendering ┃ 100.00 FPS"
+        );
+
+        let eval_output = EvaluationOutput {
+            items: vec![
+                get_item(0, 20.0, Some(0.3)),
+                get_item(1, 30.0, Some(0.1)),
+            ],
+            ..eval_output
+        };
+        let summary = eval_output.summary();
+        assert!(summary.to_string().contains("LPIPS ┃ Mean 0.2000"));
+        assert_eq!(eval_output.to_string(), summary.to_string());
+    }
+
+    #[test]
+    fn tester_manifest() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let mut config = experiment::tests::get_test_config("tester_manifest");
+        config.epoch_count = 1;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+        let output = experiment.tester.test(renderer).unwrap();
+
+        let artifact_directory = experiment.tester.artifact_directory;
+        let manifest =
+            TestManifest::load(artifact_directory.join("test-manifest.json"))
+                .unwrap();
+        assert_eq!(manifest.dataset_source, config.dataset_file_path_or_url);
+        assert_eq!(
+            manifest.output_file_names,
+            ["collage.png", "evaluation-output.json"]
+        );
+        for file_name in &manifest.output_file_names {
+            assert!(artifact_directory.join(file_name).is_file());
+        }
+        assert_eq!(
+            artifact_directory.join(&manifest.output_file_names[0]),
+            output.collage_path.unwrap()
+        );
+
+        let record_file_name = manifest.renderer_record_file_name.unwrap();
+        let record =
+            fs::read(artifact_directory.join(record_file_name)).unwrap();
+        assert_eq!(
+            manifest.renderer_record_hash.unwrap(),
+            format!("{:016x}", get_fnv1a_hash(&record))
+        );
+        let summary = manifest.summary.unwrap();
+        let summary_expected = output.eval_output.summary();
+        for (psnr, psnr_expected) in
+            summary.fidelity_psnr.iter().zip(summary_expected.fidelity_psnr)
+        {
+            assert!((psnr - psnr_expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn tester_alpha() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        // NOTE: The rays through the corners miss the bounding box,
+        // so they have no samples and they are fully transparent
+        let mut config = experiment::tests::get_test_config("tester_alpha");
+        config.dataset.scene_bbox = Some(([-1.0; 3], [1.0; 3]));
+        config.epoch_count = 1;
+        config.save_alpha = true;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+        let renderer = experiment.trainer.train().unwrap();
+        experiment.tester.test(renderer).unwrap();
+
+        let artifact_directory = experiment.tester.artifact_directory;
+        let manifest =
+            TestManifest::load(artifact_directory.join("test-manifest.json"))
+                .unwrap();
+        assert!(manifest
+            .output_file_names
+            .contains(&"output-000.png".to_string()));
+
+        let image = image::open(artifact_directory.join("output-000.png"))
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(image.dimensions(), (8, 8));
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert_eq!(image.get_pixel(7, 7)[3], 0);
+    }
+
+    #[test]
+    fn tester_output_clamping() {
+        let device = Default::default();
+
+        let image = Tensor::<Backend, 1>::from_floats(
+            [0.5, 0.5, 0.5, 2.0, 0.5, 0.5, -0.1, 1.5, 0.5, 1.0, 0.0, 1.0],
+            &device,
+        )
+        .reshape([2, 2, 3]);
+        assert_eq!(get_clipped_pixel_count(&image), 2);
+
+        let image_clamped = get_displayed_image(image.clone(), true);
+        assert_eq!(image_clamped.into_data(), image.clone().into_data());
+
+        let image_scaled = get_displayed_image(image, false);
+        assert_eq!(image_scaled.clone().max().into_scalar(), 1.0);
+        assert_eq!(image_scaled.slice([0..1, 0..1, 0..1]).into_scalar(), 0.25);
+    }
+
+    #[test]
+    fn median_render_time() {
+        assert_eq!(get_median(vec![30.0, 10.0, 500.0]), 30.0);
+        assert_eq!(get_median(vec![40.0, 10.0, 20.0, 900.0]), 30.0);
+        assert!(get_median(vec![]).is_nan());
     }
 }
@@ -8,38 +8,118 @@ use burn::{
     optim::{self, Optimizer},
     prelude::*,
     record,
-    tensor::backend::AutodiffBackend,
+    tensor::{backend::AutodiffBackend, Distribution},
 };
+use image::ImageFormat;
 use kdam::{term, Bar, BarExt};
+use rand::{rngs::StdRng, SeedableRng};
 use std::{
-    io::{stderr, IsTerminal},
+    env,
+    fs::{self, File},
+    io::{stderr, BufWriter, IsTerminal, Write},
     path::PathBuf,
 };
 
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum LossReduction {
+    Mean,
+    Sum,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum LrSchedule {
+    Constant,
+    // NOTE: The learning rate ramps linearly from zero for `warmup_steps`,
+    // and then it decays in a half cosine to `min_lr` at the final epoch
+    WarmupCosine {
+        warmup_steps: usize,
+        min_lr: f64,
+    },
+}
+
+#[derive(Config, Debug)]
+pub struct ProfilingConfig {
+    #[config(default = 25)]
+    pub epoch_interval: usize,
+    // NOTE: The rendered profiling image is saved as a frame in
+    // the progress directory at every interval of epochs, so the frames
+    // can be exported as an animation by `ProgressAnimationConfig`
+    #[config(default = "None")]
+    pub preview_interval: Option<usize>,
+    #[config(default = 1)]
+    pub psnr_downsampling_factor: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct Trainer<B: AutodiffBackend> {
     pub(super) artifact_directory: PathBuf,
-    pub(super) criterion: loss::MseLoss<B>,
+    pub(super) artifact_fallback_to_temp_directory: bool,
+    pub(super) color_noise_std: f32,
+    pub(super) criterion: crate::loss::ReconstructionLoss,
+    pub(super) criterion_distortion: crate::loss::DistortionLoss,
+    pub(super) criterion_opacity: crate::loss::OpacityLoss,
     pub(super) dataset: dataset::SimpleNerfDataset<B>,
     pub(super) device: B::Device,
+    pub(super) distortion_weight: f32,
     pub(super) epoch_count: usize,
+    pub(super) grad_accumulation_steps: usize,
     pub(super) learning_rate: f64,
+    pub(super) loss_reduction: LossReduction,
+    pub(super) lr_schedule: LrSchedule,
     pub(super) metric_fidelity_psnr: metric::PsnrMetric<B::InnerBackend>,
+    pub(super) opacity_reg_weight: f32,
+    pub(super) profiling: ProfilingConfig,
     pub(super) progress_bar: Bar,
     pub(super) renderer: renderer::VolumeRenderer<B>,
+    pub(super) seed: Option<u64>,
+    pub(super) weight_decay: f64,
 }
 
 impl<B: AutodiffBackend> Trainer<B> {
     pub fn train(&self) -> Result<renderer::VolumeRenderer<B::InnerBackend>> {
-        let input_profile =
-            self.dataset.get(0).map(|data| data.into_input(&self.device));
+        super::check_artifact_directory(&self.artifact_directory)?;
+
+        // Caching the Profiling Input on the Device
+        let input_profile = self.get_input_profile();
+        let profiling_interval = self.profiling.epoch_interval.max(1);
+        if let (Some(memory_budget_bytes), Some(input)) =
+            (self.renderer.memory_budget_bytes(), &input_profile)
+        {
+            let [height, width, points_per_ray, ..] = input.intervals.dims();
+            eprintln!(
+                "Rendering in {} chunks within {} bytes",
+                self.renderer
+                    .render_stats([height, width], points_per_ray)
+                    .chunk_count,
+                memory_budget_bytes
+            );
+        }
 
         let dataset_size = self.dataset.len();
         let dataset =
             transform::SamplerDataset::new(self.dataset.clone(), dataset_size);
-        let mut optimizer = optim::AdamConfig::new().init();
+        let mut optimizer = self.get_optimizer_config().init();
         let mut progress_bar = self.progress_bar.clone();
         let mut renderer = self.renderer.clone();
+        let mut rng =
+            self.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+
+        // Initializing the Training Log
+        let mut training_log = BufWriter::new(File::create(
+            self.artifact_directory.join("training-log.csv"),
+        )?);
+        writeln!(
+            training_log,
+            "epoch,loss,psnr,lr,opacity_min,opacity_mean,opacity_max,batch_psnr"
+        )?;
+
+        // Initializing the Progress Directory
+        let progress_directory = self
+            .artifact_directory
+            .join(super::progress::PROGRESS_DIRECTORY_NAME);
+        if self.profiling.preview_interval.is_some() {
+            fs::create_dir_all(&progress_directory)?;
+        }
 
         // Initializing the Progress Bar
         term::init(stderr().is_terminal());
@@ -55,37 +135,95 @@ impl<B: AutodiffBackend> Trainer<B> {
                 data.unwrap().into_input(&self.device)
             };
 
-            let output_image = renderer.forward(
-                input.directions,
-                input.intervals,
-                input.positions,
+            let (gradients, loss_value) =
+                self.get_gradients(&renderer, input, &mut rng);
+            let learning_rate = self.lr_schedule.get_learning_rate(
+                self.learning_rate,
+                epoch,
+                self.epoch_count,
             );
+            renderer = optimizer.step(learning_rate, renderer, gradients);
 
-            let loss = self.criterion.forward(
-                output_image,
-                input.image,
-                loss::Reduction::Mean,
-            );
+            // Profiling
+            let is_profiling = epoch % profiling_interval == 0;
+            let is_previewing = self
+                .profiling
+                .preview_interval
+                .is_some_and(|interval| epoch % interval.max(1) == 0);
+            let profile = if let Some(input) =
+                input_profile.as_ref().filter(|_| is_profiling || is_previewing)
+            {
+                let output = renderer.valid().forward_with_aux(
+                    input.directions.clone(),
+                    input.intervals.clone(),
+                    input.positions.clone(),
+                );
 
-            let gradients =
-                optim::GradientsParams::from_grads(loss.backward(), &renderer);
-            renderer = optimizer.step(self.learning_rate, renderer, gradients);
+                // Saving the Preview
+                if is_previewing {
+                    super::tester::get_rgb_image(output.image.clone())?
+                        .save_with_format(
+                            progress_directory.join(
+                                super::progress::get_frame_file_name(epoch),
+                            ),
+                            ImageFormat::Png,
+                        )?;
+                }
 
-            // Profiling
-            if input_profile.is_some() && epoch % 25 == 0 {
-                let input = input_profile.clone().unwrap();
+                if is_profiling {
+                    let opacity_stats = output.get_opacity_stats();
 
-                let output_image = renderer.valid().forward(
-                    input.directions,
-                    input.intervals,
-                    input.positions,
-                );
+                    // NOTE: The PSNR is always computed from the mean error,
+                    // so it is comparable regardless of the loss reduction
+                    let fidelity_psnr = self
+                        .metric_fidelity_psnr
+                        .forward_downsampled(
+                            output.image,
+                            input.image.clone(),
+                            self.profiling.psnr_downsampling_factor,
+                        )
+                        .into_scalar();
+                    progress_bar.postfix = format!(
+                        "┃ PSNR = {:.2} dB ┃ Opacity = {:.3}/{:.3}/{:.3}",
+                        fidelity_psnr,
+                        opacity_stats[0],
+                        opacity_stats[1],
+                        opacity_stats[2]
+                    );
+                    Some((fidelity_psnr, opacity_stats))
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
 
-                let fidelity_psnr = self
-                    .metric_fidelity_psnr
-                    .forward(output_image, input.image)
-                    .into_scalar();
-                progress_bar.postfix = format!("┃ PSNR = {:.2} dB", fidelity_psnr);
+            // Logging
+            // NOTE: The batch PSNR is derived from the plain MSE loss of
+            // the training image before the step by `from_mse` without
+            // another metric pass, so it is not the PSNR of the profiling
+            // view, and it is empty for the other losses
+            let batch_psnr = self.is_loss_mse().then(|| {
+                self.metric_fidelity_psnr
+                    .from_mse(Tensor::from_floats([loss_value], &self.device))
+                    .into_scalar()
+            });
+            // NOTE: The log is flushed at each profiling step,
+            // so a crashed run still has the partial logs
+            writeln!(
+                training_log,
+                "{},{},{},{},{},{}",
+                epoch,
+                loss_value,
+                profile.map_or(String::new(), |(psnr, _)| psnr.to_string()),
+                learning_rate,
+                profile.map_or(",,".into(), |(_, stats)| stats
+                    .map(|stat| stat.to_string())
+                    .join(",")),
+                batch_psnr.map_or(String::new(), |psnr| psnr.to_string()),
+            )?;
+            if profile.is_some() {
+                training_log.flush()?;
             }
 
             progress_bar.update(1)?;
@@ -107,12 +245,649 @@ impl<B: AutodiffBackend> Trainer<B> {
             progress_bar.refresh()?;
         }
 
+        // Evaluating on the Training Set
+        // NOTE: The final row of the log has the mean PSNR of all the items,
+        // and it is omitted for the empty training set
+        if let Some(training_psnr) = self.get_training_psnr(&renderer.valid()) {
+            eprintln!("Training set ┃ Mean PSNR = {:.2} dB", training_psnr);
+            writeln!(training_log, "final,,{},,,,,", training_psnr)?;
+        }
+        training_log.flush()?;
+
         // Saving the Renderer
-        renderer.clone().save_file(
+        let recorder = record::DefaultRecorder::new();
+        let result = renderer.clone().save_file(
             self.artifact_directory.join("volume-renderer"),
-            &record::DefaultRecorder::new(),
-        )?;
+            &recorder,
+        );
+        if let Err(error) = result {
+            if !self.artifact_fallback_to_temp_directory {
+                return Err(error.into());
+            }
+
+            let fallback_directory = env::temp_dir().join("simple-nerf");
+            fs::create_dir_all(&fallback_directory)?;
+            eprintln!(
+                "Failed to save the renderer in {:?} ({}), saving it in {:?}",
+                self.artifact_directory, error, fallback_directory
+            );
+            renderer.clone().save_file(
+                fallback_directory.join("volume-renderer"),
+                &recorder,
+            )?;
+        }
 
         Ok(renderer.valid())
     }
+
+    // NOTE: The image is split into the row chunks of rays. Each chunk loss
+    // is weighted by its share of rays under the mean reduction, so the
+    // accumulated gradients are the average over the chunks
+    fn get_gradients(
+        &self,
+        renderer: &renderer::VolumeRenderer<B>,
+        input: dataset::SimpleNerfInput<B>,
+        rng: &mut StdRng,
+    ) -> (optim::GradientsParams, f32) {
+        let chunk_count = self.grad_accumulation_steps.max(1);
+        if chunk_count == 1 {
+            let output = renderer.forward_with_aux(
+                input.directions,
+                input.intervals.clone(),
+                input.positions,
+            );
+            let loss = self.get_loss(output, input.image, input.intervals, rng);
+            let loss_value = loss.clone().into_scalar().elem::<f32>();
+            let gradients =
+                optim::GradientsParams::from_grads(loss.backward(), renderer);
+            return (gradients, loss_value);
+        }
+
+        let [height, ..] = input.image.dims();
+        let mut accumulator = optim::GradientsAccumulator::new();
+        let mut loss_value = 0.0;
+        for (((directions, image), intervals), positions) in input
+            .directions
+            .chunk(chunk_count, 0)
+            .into_iter()
+            .zip(input.image.chunk(chunk_count, 0))
+            .zip(input.intervals.chunk(chunk_count, 0))
+            .zip(input.positions.chunk(chunk_count, 0))
+        {
+            let weight = match self.loss_reduction {
+                LossReduction::Mean => image.dims()[0] as f32 / height as f32,
+                LossReduction::Sum => 1.0,
+            };
+            let output = renderer.forward_with_aux(
+                directions,
+                intervals.clone(),
+                positions,
+            );
+            let loss = self.get_loss(output, image, intervals, rng) * weight;
+            loss_value += loss.clone().into_scalar().elem::<f32>();
+            accumulator.accumulate(
+                renderer,
+                optim::GradientsParams::from_grads(loss.backward(), renderer),
+            );
+        }
+
+        (accumulator.grads(), loss_value)
+    }
+
+    fn get_loss(
+        &self,
+        output: renderer::VolumeRendererOutput<B>,
+        image: Tensor<B, 3>,
+        intervals: Tensor<B, 4>,
+        rng: &mut StdRng,
+    ) -> Tensor<B, 1> {
+        // NOTE: The background rays are found before jittering the colors
+        let loss_opacity = if self.opacity_reg_weight > 0.0 {
+            Some(
+                self.criterion_opacity
+                    .forward(output.accumulated_opacity, image.clone())
+                    * self.opacity_reg_weight,
+            )
+        } else {
+            None
+        };
+
+        // NOTE: Jittering the ground-truth colors against overfitting
+        let image = if self.color_noise_std > 0.0 {
+            let noises = Tensor::from_data(
+                Data::<f32, 3>::random(
+                    image.shape(),
+                    Distribution::Normal(0.0, self.color_noise_std as f64),
+                    rng,
+                )
+                .convert(),
+                &self.device,
+            );
+            image + noises
+        } else {
+            image
+        };
+
+        let loss = self.criterion.forward(
+            output.image,
+            image,
+            self.loss_reduction.into(),
+        );
+
+        let loss = if self.distortion_weight > 0.0 {
+            loss + self.criterion_distortion.forward(output.weights, intervals)
+                * self.distortion_weight
+        } else {
+            loss
+        };
+
+        match loss_opacity {
+            Some(loss_opacity) => loss + loss_opacity,
+            None => loss,
+        }
+    }
+
+    // NOTE: The PSNR is averaged over the items rendered with the noiseless
+    // distances, and it is `None` without items
+    fn get_training_psnr(
+        &self,
+        renderer: &renderer::VolumeRenderer<B::InnerBackend>,
+    ) -> Option<f32> {
+        let psnrs = self
+            .dataset
+            .clone()
+            .into_noiseless()
+            .iter()
+            .map(|data| {
+                let input = data.into_input(&self.device);
+                let output = renderer.forward(
+                    input.directions,
+                    input.intervals,
+                    input.positions,
+                );
+                self.metric_fidelity_psnr
+                    .forward(output, input.image)
+                    .into_scalar()
+                    .elem::<f32>()
+            })
+            .collect::<Vec<_>>();
+        (!psnrs.is_empty())
+            .then(|| psnrs.iter().sum::<f32>() / psnrs.len() as f32)
+    }
+
+    // NOTE: The loss is the mean squared error of the colors
+    // without any jittering or regularization
+    fn is_loss_mse(&self) -> bool {
+        self.criterion == crate::loss::ReconstructionLoss::Mse
+            && self.loss_reduction == LossReduction::Mean
+            && self.color_noise_std <= 0.0
+            && self.distortion_weight <= 0.0
+            && self.opacity_reg_weight <= 0.0
+    }
+
+    fn get_optimizer_config(&self) -> optim::AdamConfig {
+        let weight_decay = Some(self.weight_decay)
+            .filter(|weight_decay| *weight_decay > 0.0)
+            .map(optim::decay::WeightDecayConfig::new);
+        optim::AdamConfig::new().with_weight_decay(weight_decay)
+    }
+
+    fn get_input_profile(
+        &self
+    ) -> Option<dataset::SimpleNerfInput<B::InnerBackend>> {
+        self.dataset.get(0).map(|data| data.into_input(&self.device))
+    }
+}
+
+impl LrSchedule {
+    pub fn get_learning_rate(
+        &self,
+        learning_rate: f64,
+        epoch: usize,
+        epoch_count: usize,
+    ) -> f64 {
+        match *self {
+            LrSchedule::Constant => learning_rate,
+            LrSchedule::WarmupCosine {
+                warmup_steps,
+                min_lr,
+            } => {
+                if epoch < warmup_steps {
+                    return learning_rate * epoch as f64 / warmup_steps as f64;
+                }
+                let decay_steps = epoch_count.saturating_sub(warmup_steps + 1);
+                let progress = if decay_steps == 0 {
+                    1.0
+                } else {
+                    ((epoch - warmup_steps) as f64 / decay_steps as f64)
+                        .min(1.0)
+                };
+                min_lr
+                    + (learning_rate - min_lr)
+                        * (1.0 + (progress * std::f64::consts::PI).cos())
+                        / 2.0
+            },
+        }
+    }
+}
+
+impl From<LossReduction> for loss::Reduction {
+    fn from(reduction: LossReduction) -> Self {
+        match reduction {
+            LossReduction::Mean => loss::Reduction::Mean,
+            LossReduction::Sum => loss::Reduction::Sum,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use experiment::tests::*;
+
+    type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+    #[test]
+    fn trainer_input_profile_caching() {
+        let device = Default::default();
+
+        let mut config = get_test_config("trainer_input_profile_caching");
+        config.epoch_count = 20;
+        config.profiling.epoch_interval = 5;
+        let experiment = config.init::<Backend>(&device, true).unwrap();
+
+        // One input is moved onto the device for each training step and
+        // each item evaluated after training, and only one more for all
+        // the 4 profiling steps
+        let count = dataset::tests::INPUT_COUNT.with(|count| count.get());
+        let item_count = experiment.trainer.dataset.len();
+        let renderer = experiment.trainer.train();
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+        assert_eq!(
+            dataset::tests::INPUT_COUNT.with(|count| count.get()) - count,
+            20 + item_count + 1
+        );
+    }
+
+    #[test]
+    fn trainer_color_noise() {
+        let device = Default::default();
+
+        let config = get_test_config("trainer_color_noise");
+        let mut trainer =
+            config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let input = trainer.dataset.get(0).unwrap().into_input(&device);
+        let image = input.image;
+        let intervals = input.intervals;
+        let output = trainer.renderer.forward_with_aux(
+            input.directions,
+            intervals.clone(),
+            input.positions,
+        );
+        let get_loss = |trainer: &Trainer<Backend>, seed| {
+            trainer
+                .get_loss(
+                    output.clone(),
+                    image.clone(),
+                    intervals.clone(),
+                    &mut StdRng::seed_from_u64(seed),
+                )
+                .into_scalar()
+        };
+
+        let loss_true = trainer
+            .criterion
+            .forward(output.image.clone(), image.clone(), loss::Reduction::Mean)
+            .into_scalar();
+        assert_eq!(get_loss(&trainer, 0), loss_true);
+
+        trainer.color_noise_std = 0.1;
+        let loss = get_loss(&trainer, 7);
+        assert_ne!(loss, loss_true);
+        assert_eq!(loss, get_loss(&trainer, 7));
+        assert_ne!(loss, get_loss(&trainer, 8));
+    }
+
+    #[test]
+    fn trainer_loss_reduction() {
+        let device = Default::default();
+
+        let config = get_test_config("trainer_loss_reduction");
+        let mut trainer =
+            config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let input = trainer.dataset.get(0).unwrap().into_input(&device);
+        let [height, width, points_per_ray, ..] = input.intervals.dims();
+        let image_output = input.image.random_like(Distribution::Default);
+        let get_gradient = |trainer: &Trainer<Backend>| {
+            let image_output = image_output.clone().require_grad();
+            let output = renderer::VolumeRendererOutput {
+                accumulated_opacity: Tensor::zeros([height, width, 1], &device),
+                colors: Tensor::zeros(
+                    [height, width, points_per_ray, 3],
+                    &device,
+                ),
+                image: image_output.clone(),
+                opacities: Tensor::zeros(
+                    [height, width, points_per_ray, 1],
+                    &device,
+                ),
+                weights: Tensor::zeros(
+                    [height, width, points_per_ray, 1],
+                    &device,
+                ),
+            };
+            let gradients = trainer
+                .get_loss(
+                    output,
+                    input.image.clone(),
+                    input.intervals.clone(),
+                    &mut StdRng::seed_from_u64(0),
+                )
+                .backward();
+            image_output.grad(&gradients).unwrap()
+        };
+
+        assert_eq!(trainer.loss_reduction, LossReduction::Mean);
+        let gradient_mean = get_gradient(&trainer);
+        trainer.loss_reduction = LossReduction::Sum;
+        let gradient_sum = get_gradient(&trainer);
+
+        let count = (height * width * 3) as f32;
+        (gradient_mean * count)
+            .into_data()
+            .assert_approx_eq(&gradient_sum.into_data(), 4);
+    }
+
+    #[test]
+    fn trainer_loss_mse() {
+        let device = Default::default();
+
+        let config = get_test_config("trainer_loss_mse");
+        let mut trainer =
+            config.init::<Backend>(&device, true).unwrap().trainer;
+        assert!(trainer.is_loss_mse());
+
+        trainer.loss_reduction = LossReduction::Sum;
+        assert!(!trainer.is_loss_mse());
+        trainer.loss_reduction = LossReduction::Mean;
+        trainer.distortion_weight = 0.01;
+        assert!(!trainer.is_loss_mse());
+        trainer.distortion_weight = 0.0;
+        trainer.criterion = crate::loss::ReconstructionLoss::L1;
+        assert!(!trainer.is_loss_mse());
+    }
+
+    #[test]
+    fn trainer_grad_accumulation() {
+        struct GradientsCollector<'a> {
+            gradients: &'a optim::GradientsParams,
+            values: Vec<f32>,
+        }
+
+        impl<'a> burn::module::ModuleVisitor<Backend> for GradientsCollector<'a> {
+            fn visit_float<const D: usize>(
+                &mut self,
+                id: &burn::module::ParamId,
+                _tensor: &Tensor<Backend, D>,
+            ) {
+                let gradient = self
+                    .gradients
+                    .get::<burn::backend::NdArray, D>(id)
+                    .unwrap();
+                self.values.extend(gradient.into_data().convert::<f32>().value);
+            }
+        }
+
+        let device = Default::default();
+
+        let config = get_test_config("trainer_grad_accumulation");
+        let mut trainer =
+            config.init::<Backend>(&device, true).unwrap().trainer;
+        let input = trainer.dataset.get(0).unwrap().into_input(&device);
+
+        let get_gradients = |trainer: &Trainer<Backend>| {
+            let (gradients, loss_value) = trainer.get_gradients(
+                &trainer.renderer,
+                input.clone(),
+                &mut StdRng::seed_from_u64(0),
+            );
+            let mut collector = GradientsCollector {
+                gradients: &gradients,
+                values: vec![],
+            };
+            trainer.renderer.visit(&mut collector);
+            (collector.values, loss_value)
+        };
+
+        let (gradients, loss_value) = {
+            let output = trainer.renderer.forward_with_aux(
+                input.directions.clone(),
+                input.intervals.clone(),
+                input.positions.clone(),
+            );
+            let loss = trainer.get_loss(
+                output,
+                input.image.clone(),
+                input.intervals.clone(),
+                &mut StdRng::seed_from_u64(0),
+            );
+            let loss_value = loss.clone().into_scalar();
+            let gradients = optim::GradientsParams::from_grads(
+                loss.backward(),
+                &trainer.renderer,
+            );
+            let mut collector = GradientsCollector {
+                gradients: &gradients,
+                values: vec![],
+            };
+            trainer.renderer.visit(&mut collector);
+            (collector.values, loss_value)
+        };
+        assert!(!gradients.is_empty());
+
+        assert_eq!(trainer.grad_accumulation_steps, 1);
+        assert_eq!(get_gradients(&trainer), (gradients.clone(), loss_value));
+
+        trainer.grad_accumulation_steps = 4;
+        let (gradients_accumulated, loss_value_accumulated) =
+            get_gradients(&trainer);
+        Data::from(gradients_accumulated.as_slice())
+            .assert_approx_eq(&Data::from(gradients.as_slice()), 4);
+        assert!((loss_value_accumulated - loss_value).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lr_schedule_warmup_cosine() {
+        let schedule = LrSchedule::WarmupCosine {
+            warmup_steps: 10,
+            min_lr: 1e-5,
+        };
+        let get_learning_rate =
+            |epoch| schedule.get_learning_rate(1e-3, epoch, 101);
+
+        assert_eq!(get_learning_rate(0), 0.0);
+        assert!((get_learning_rate(5) - 5e-4).abs() < 1e-12);
+        assert!((get_learning_rate(10) - 1e-3).abs() < 1e-12);
+        assert!((get_learning_rate(55) - (1e-3 + 1e-5) / 2.0).abs() < 1e-12);
+        assert!((get_learning_rate(100) - 1e-5).abs() < 1e-12);
+        assert!((1..=100).all(|epoch| get_learning_rate(epoch) > 0.0
+            && (epoch <= 10
+                || get_learning_rate(epoch) < get_learning_rate(epoch - 1))));
+
+        assert_eq!(LrSchedule::Constant.get_learning_rate(1e-3, 7, 10), 1e-3);
+    }
+
+    #[test]
+    fn trainer_weight_decay() {
+        let device = Default::default();
+
+        let config = get_test_config("trainer_weight_decay");
+        let mut trainer =
+            config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let get_optimizer_config = |trainer: &Trainer<Backend>| {
+            trainer.get_optimizer_config().to_string().replace([' ', '\n'], "")
+        };
+
+        assert!(
+            get_optimizer_config(&trainer).contains("\"weight_decay\":null")
+        );
+        trainer.weight_decay = 0.01;
+        assert!(get_optimizer_config(&trainer)
+            .contains("\"weight_decay\":{\"penalty\":0.01}"));
+    }
+
+    #[test]
+    fn trainer_training_log() {
+        let device = Default::default();
+
+        let mut config = get_test_config("trainer_training_log");
+        config.epoch_count = 3;
+        config.profiling.epoch_interval = 2;
+        let trainer = config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let renderer = trainer.train();
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+
+        let training_log = std::fs::read_to_string(
+            trainer.artifact_directory.join("training-log.csv"),
+        )
+        .unwrap();
+        let rows = training_log
+            .lines()
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[0],
+            [
+                "epoch",
+                "loss",
+                "psnr",
+                "lr",
+                "opacity_min",
+                "opacity_mean",
+                "opacity_max",
+                "batch_psnr"
+            ]
+        );
+        for (epoch, row) in rows[1..4].iter().enumerate() {
+            assert_eq!(row.len(), 8);
+            assert_eq!(row[0], epoch.to_string());
+            assert!(row[1].parse::<f32>().is_ok());
+            assert_eq!(row[2].parse::<f32>().is_ok(), epoch % 2 == 0);
+            assert_eq!(row[3], "0.001");
+            for stat in &row[4..7] {
+                assert_eq!(
+                    stat.parse::<f32>().is_ok_and(|stat| stat >= 0.0),
+                    epoch % 2 == 0
+                );
+            }
+        }
+
+        // The batch PSNR is logged at every epoch for the plain MSE loss
+        for row in &rows[1..4] {
+            assert!(row[7].parse::<f32>().is_ok_and(f32::is_finite));
+        }
+
+        // The final row has the mean PSNR on the training set
+        assert_eq!(rows[4].len(), 8);
+        assert_eq!(rows[4][0], "final");
+        assert!(rows[4][2].parse::<f32>().is_ok_and(f32::is_finite));
+    }
+
+    #[test]
+    fn trainer_training_psnr() {
+        let device = Default::default();
+
+        // The training set has the noisy distances, but its PSNR is
+        // evaluated with the noiseless ones
+        let config = get_test_config("trainer_training_psnr");
+        let trainer = config.init::<Backend>(&device, true).unwrap().trainer;
+        assert!(trainer.dataset.has_noisy_distance());
+        let renderer = trainer.renderer.valid();
+        let training_psnr = trainer.get_training_psnr(&renderer);
+        assert!(training_psnr.is_some_and(f32::is_finite));
+        assert_eq!(trainer.get_training_psnr(&renderer), training_psnr);
+
+        // The empty training set has neither the PSNR nor the final row
+        let mut config = get_test_config("trainer_training_psnr_empty");
+        config.epoch_count = 1;
+        config.train_ratio = 0.0;
+        let trainer = config.init::<Backend>(&device, true).unwrap().trainer;
+        assert_eq!(trainer.get_training_psnr(&trainer.renderer.valid()), None);
+
+        let renderer = trainer.train();
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+
+        let training_log = std::fs::read_to_string(
+            trainer.artifact_directory.join("training-log.csv"),
+        )
+        .unwrap();
+        assert_eq!(training_log.lines().count(), 1);
+    }
+
+    #[test]
+    fn trainer_profiling_interval() {
+        let device = Default::default();
+
+        let mut config = get_test_config("trainer_profiling_interval");
+        config.epoch_count = 10;
+        config.profiling.epoch_interval = 5;
+        let trainer = config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let renderer = trainer.train();
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+
+        // The PSNR is logged whenever the progress bar postfix is updated
+        let training_log = std::fs::read_to_string(
+            trainer.artifact_directory.join("training-log.csv"),
+        )
+        .unwrap();
+        let profiled_epochs = training_log
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .filter(|row| row[0] != "final" && !row[2].is_empty())
+            .map(|row| row[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(profiled_epochs, ["0", "5"]);
+    }
+
+    #[test]
+    fn trainer_preview() {
+        let device = Default::default();
+
+        let mut config = get_test_config("trainer_preview");
+        config.epoch_count = 5;
+        config.profiling.preview_interval = Some(2);
+        let trainer = config.init::<Backend>(&device, true).unwrap().trainer;
+
+        let renderer = trainer.train();
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+
+        let progress_directory = trainer
+            .artifact_directory
+            .join(experiment::progress::PROGRESS_DIRECTORY_NAME);
+        let mut previews = std::fs::read_dir(&progress_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        previews.sort();
+        assert_eq!(
+            previews,
+            ["epoch-00000.png", "epoch-00002.png", "epoch-00004.png"]
+        );
+
+        // The previews are exported as the frames of an animation
+        let epochs = experiment::progress::ProgressAnimationConfig::new()
+            .export(
+                &progress_directory,
+                trainer.artifact_directory.join("progress.gif"),
+            )
+            .unwrap();
+        assert_eq!(epochs, [0, 2, 4]);
+    }
 }
@@ -1,15 +1,23 @@
 extern crate anyhow;
+extern crate bincode;
 extern crate burn;
-extern crate kdam;
 extern crate image;
+extern crate kdam;
 extern crate npyz;
+extern crate rand;
+extern crate rayon;
 extern crate regex;
 extern crate reqwest;
+extern crate serde;
 extern crate zip;
 
+pub mod cache;
 pub mod dataset;
+pub mod diagnostic;
 pub mod encoder;
 pub mod experiment;
+pub mod loss;
+pub mod mesh;
 pub mod metric;
 pub mod renderer;
 pub mod scene;
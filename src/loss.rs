@@ -0,0 +1,272 @@
+use burn::{nn::loss, prelude::*};
+
+#[derive(Clone, Debug, Default)]
+pub struct DistortionLoss;
+
+#[derive(Clone, Debug, Default)]
+pub struct OpacityLoss;
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum ReconstructionLoss {
+    Mse,
+    L1,
+    Charbonnier {
+        epsilon: f32,
+    },
+    Huber {
+        delta: f32,
+    },
+}
+
+impl DistortionLoss {
+    pub fn new() -> Self {
+        Self
+    }
+
+    // NOTE: The loss is `sum(w_i * w_j * |m_i - m_j|) + sum(w_i^2 * d_i) / 3`,
+    // where `m` are the interval midpoints and `d` are the interval lengths
+    pub fn forward<B: Backend>(
+        &self,
+        weights: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+    ) -> Tensor<B, 1> {
+        let [height, width, points_per_ray, ..] = weights.dims();
+        let device = weights.device();
+        let weights = weights.reshape([height * width, points_per_ray]);
+
+        // NOTE: The last interval is a sentinel, so the one before it is reused
+        let intervals = {
+            let intervals = intervals.reshape([height * width, points_per_ray]);
+            let last = points_per_ray.max(2) - 2;
+            Tensor::cat(
+                vec![
+                    intervals
+                        .clone()
+                        .slice([0..height * width, 0..points_per_ray - 1]),
+                    intervals.slice([0..height * width, last..last + 1]),
+                ],
+                1,
+            )
+        };
+
+        let midpoints = {
+            let offsets = intervals.clone().matmul(
+                Tensor::<B, 2>::ones([points_per_ray, points_per_ray], &device)
+                    .triu(1),
+            );
+            offsets + intervals.clone() / 2.0
+        };
+
+        let loss_inter = {
+            let distances = (midpoints.clone().unsqueeze_dim::<3>(2)
+                - midpoints.unsqueeze_dim::<3>(1))
+            .abs();
+            (weights.clone().unsqueeze_dim::<3>(2)
+                * weights.clone().unsqueeze_dim::<3>(1)
+                * distances)
+                .sum_dim(2)
+                .sum_dim(1)
+                .reshape([-1])
+        };
+
+        let loss_intra =
+            (weights.powf_scalar(2.0) * intervals).sum_dim(1).reshape([-1])
+                / 3.0;
+
+        (loss_inter + loss_intra).mean()
+    }
+}
+
+impl OpacityLoss {
+    pub fn new() -> Self {
+        Self
+    }
+
+    // NOTE: The loss is the mean accumulated opacity of the background rays,
+    // whose target pixels are black as the renderer composites over black
+    pub fn forward<B: Backend>(
+        &self,
+        accumulated_opacity: Tensor<B, 3>,
+        image: Tensor<B, 3>,
+    ) -> Tensor<B, 1> {
+        let backgrounds =
+            image.abs().max_dim(2).lower_equal_elem(1.0 / 255.0).float();
+        let count = backgrounds.clone().sum().clamp_min(1.0);
+
+        (accumulated_opacity * backgrounds).sum() / count
+    }
+}
+
+impl ReconstructionLoss {
+    pub fn forward<B: Backend, const D: usize>(
+        &self,
+        logits: Tensor<B, D>,
+        targets: Tensor<B, D>,
+        reduction: loss::Reduction,
+    ) -> Tensor<B, 1> {
+        let errors = logits - targets;
+        let losses = match *self {
+            ReconstructionLoss::Mse => errors.powf_scalar(2.0),
+            ReconstructionLoss::L1 => errors.abs(),
+            ReconstructionLoss::Charbonnier {
+                epsilon,
+            } => (errors.powf_scalar(2.0) + epsilon * epsilon).sqrt(),
+            // NOTE: The loss is quadratic within `delta` and linear beyond it
+            ReconstructionLoss::Huber {
+                delta,
+            } => {
+                let errors = errors.abs();
+                let is_linear = errors.clone().greater_elem(delta);
+                (errors.clone().powf_scalar(2.0) / 2.0)
+                    .mask_where(is_linear, (errors - delta / 2.0) * delta)
+            },
+        };
+
+        match reduction {
+            loss::Reduction::Sum => losses.sum(),
+            _ => losses.mean(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Backend = burn::backend::NdArray;
+
+    #[test]
+    fn reconstruction_loss_output() {
+        let device = Default::default();
+
+        let logits = Tensor::<Backend, 2>::from_floats(
+            [[0.0, 0.1, 0.2], [0.5, 0.4, 3.0]],
+            &device,
+        );
+        let targets = Tensor::<Backend, 2>::from_floats(
+            [[0.5, 0.6, 0.7], [0.0, 0.9, 0.8]],
+            &device,
+        );
+        let get_loss = |criterion: ReconstructionLoss, reduction| {
+            criterion
+                .forward(logits.clone(), targets.clone(), reduction)
+                .into_data()
+        };
+
+        // The MSE matches the existing criterion
+        get_loss(ReconstructionLoss::Mse, loss::Reduction::Mean)
+            .assert_approx_eq(
+                &loss::MseLoss::new()
+                    .forward(
+                        logits.clone(),
+                        targets.clone(),
+                        loss::Reduction::Mean,
+                    )
+                    .into_data(),
+                6,
+            );
+        get_loss(ReconstructionLoss::Mse, loss::Reduction::Sum)
+            .assert_approx_eq(
+                &loss::MseLoss::new()
+                    .forward(
+                        logits.clone(),
+                        targets.clone(),
+                        loss::Reduction::Sum,
+                    )
+                    .into_data(),
+                6,
+            );
+
+        // The Charbonnier loss with tiny epsilon approximates the L1 loss
+        let loss_l1 = get_loss(ReconstructionLoss::L1, loss::Reduction::Mean);
+        assert!((loss_l1.value[0] - 4.7 / 6.0).abs() < 1e-6);
+        get_loss(
+            ReconstructionLoss::Charbonnier {
+                epsilon: 1e-6,
+            },
+            loss::Reduction::Mean,
+        )
+        .assert_approx_eq(&loss_l1, 5);
+
+        // The Huber loss is quadratic for the small errors only
+        let loss_huber = get_loss(
+            ReconstructionLoss::Huber {
+                delta: 1.0,
+            },
+            loss::Reduction::Sum,
+        );
+        assert!((loss_huber.value[0] - (5.0 * 0.125 + 1.7)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn distortion_loss_output() {
+        let device = Default::default();
+        let loss = DistortionLoss::new();
+
+        let intervals = Tensor::<Backend, 1>::from_floats(
+            [0.5, 0.5, 0.0, 0.5, 1e9],
+            &device,
+        )
+        .reshape([1, 1, 5, 1]);
+
+        let weights_concentrated = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.0, 1.0, 0.0, 0.0],
+            &device,
+        )
+        .reshape([1, 1, 5, 1]);
+        let distortion =
+            loss.forward(weights_concentrated, intervals.clone()).into_scalar();
+        assert_eq!(distortion, 0.0);
+
+        let weights_concentrated = Tensor::<Backend, 1>::from_floats(
+            [0.0, 1.0, 0.0, 0.0, 0.0],
+            &device,
+        )
+        .reshape([1, 1, 5, 1]);
+        let distortion =
+            loss.forward(weights_concentrated, intervals.clone()).into_scalar();
+        assert!((distortion - 0.5 / 3.0).abs() < 1e-6);
+
+        let weights_spread = Tensor::<Backend, 1>::from_floats(
+            [0.5, 0.0, 0.0, 0.0, 0.5],
+            &device,
+        )
+        .reshape([1, 1, 5, 1]);
+        let distortion = loss.forward(weights_spread, intervals).into_scalar();
+        assert!(distortion > 0.5);
+        assert!(distortion.is_finite());
+    }
+
+    #[test]
+    fn opacity_loss_output() {
+        let device = Default::default();
+        let loss = OpacityLoss::new();
+
+        let image = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.0, 0.0, 0.8, 0.5, 0.2],
+            &device,
+        )
+        .reshape([1, 2, 3]);
+
+        // The transparent background rays are not penalized
+        let accumulated_opacity =
+            Tensor::<Backend, 1>::from_floats([0.0, 1.0], &device)
+                .reshape([1, 2, 1]);
+        let opacity =
+            loss.forward(accumulated_opacity, image.clone()).into_scalar();
+        assert_eq!(opacity, 0.0);
+
+        let accumulated_opacity =
+            Tensor::<Backend, 1>::from_floats([0.25, 1.0], &device)
+                .reshape([1, 2, 1]);
+        let opacity =
+            loss.forward(accumulated_opacity, image.clone()).into_scalar();
+        assert!((opacity - 0.25).abs() < 1e-6);
+
+        // The images without background rays are not penalized
+        let opacity = loss
+            .forward(Tensor::ones([1, 2, 1], &device), image.ones_like())
+            .into_scalar();
+        assert_eq!(opacity, 0.0);
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+// NOTE: Each cube is split into 6 tetrahedra around its main diagonal,
+// so every cell can be triangulated without the ambiguous cases
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+impl Mesh {
+    pub fn from_density_grid(
+        densities: &[f32],
+        resolution: usize,
+        bounds: ([f32; 3], [f32; 3]),
+        density_threshold: f32,
+    ) -> Mesh {
+        let mut mesh = Mesh::default();
+        if resolution < 2 || densities.len() != resolution.pow(3) {
+            return mesh;
+        }
+
+        let (lower, upper) = bounds;
+        let index_of = |x: usize, y: usize, z: usize| {
+            (x * resolution + y) * resolution + z
+        };
+        let position_of = |index: usize| {
+            let coordinates = [
+                index / (resolution * resolution),
+                index / resolution % resolution,
+                index % resolution,
+            ];
+            let mut position = [0.0; 3];
+            for axis in 0..3 {
+                position[axis] = lower[axis]
+                    + (upper[axis] - lower[axis]) * coordinates[axis] as f32
+                        / (resolution - 1) as f32;
+            }
+            position
+        };
+
+        let mut edge_vertices = HashMap::<(usize, usize), u32>::new();
+        let mut vertex_on_edge = |mesh: &mut Mesh, a: usize, b: usize| {
+            let key = (a.min(b), a.max(b));
+            *edge_vertices.entry(key).or_insert_with(|| {
+                let (density_a, density_b) = (densities[a], densities[b]);
+                let ratio =
+                    (density_threshold - density_a) / (density_b - density_a);
+                let ratio = if ratio.is_finite() {
+                    ratio.clamp(0.0, 1.0)
+                } else {
+                    0.5
+                };
+                let (position_a, position_b) = (position_of(a), position_of(b));
+                let mut position = [0.0; 3];
+                for axis in 0..3 {
+                    position[axis] = position_a[axis]
+                        + (position_b[axis] - position_a[axis]) * ratio;
+                }
+                mesh.vertices.push(position);
+                (mesh.vertices.len() - 1) as u32
+            })
+        };
+
+        for x in 0..resolution - 1 {
+            for y in 0..resolution - 1 {
+                for z in 0..resolution - 1 {
+                    let corners: Vec<usize> = (0..8)
+                        .map(|corner| {
+                            index_of(
+                                x + (corner & 1),
+                                y + (corner >> 1 & 1),
+                                z + (corner >> 2 & 1),
+                            )
+                        })
+                        .collect();
+
+                    for tetrahedron in CUBE_TETRAHEDRA.iter() {
+                        let points = tetrahedron.map(|corner| corners[corner]);
+                        let (insides, outsides): (Vec<usize>, Vec<usize>) =
+                            points.iter().partition(|&&point| {
+                                densities[point] > density_threshold
+                            });
+
+                        let faces = match insides.len() {
+                            1 | 3 => {
+                                let (apex, bases) = if insides.len() == 1 {
+                                    (insides[0], &outsides)
+                                } else {
+                                    (outsides[0], &insides)
+                                };
+                                vec![[
+                                    vertex_on_edge(&mut mesh, apex, bases[0]),
+                                    vertex_on_edge(&mut mesh, apex, bases[1]),
+                                    vertex_on_edge(&mut mesh, apex, bases[2]),
+                                ]]
+                            },
+                            2 => {
+                                let a = vertex_on_edge(
+                                    &mut mesh,
+                                    insides[0],
+                                    outsides[0],
+                                );
+                                let b = vertex_on_edge(
+                                    &mut mesh,
+                                    insides[0],
+                                    outsides[1],
+                                );
+                                let c = vertex_on_edge(
+                                    &mut mesh,
+                                    insides[1],
+                                    outsides[1],
+                                );
+                                let d = vertex_on_edge(
+                                    &mut mesh,
+                                    insides[1],
+                                    outsides[0],
+                                );
+                                vec![[a, b, c], [a, c, d]]
+                            },
+                            _ => vec![],
+                        };
+
+                        // NOTE: Orienting the normals from inside to outside
+                        let inside_to_outside = {
+                            let mut offset = [0.0; 3];
+                            for &point in points.iter() {
+                                let sign =
+                                    if densities[point] > density_threshold {
+                                        -1.0 / insides.len() as f32
+                                    } else {
+                                        1.0 / outsides.len() as f32
+                                    };
+                                let position = position_of(point);
+                                for axis in 0..3 {
+                                    offset[axis] += sign * position[axis];
+                                }
+                            }
+                            offset
+                        };
+                        for face in faces {
+                            let normal = Mesh::get_normal(&mesh, face);
+                            let alignment = (0..3)
+                                .map(|axis| {
+                                    normal[axis] * inside_to_outside[axis]
+                                })
+                                .sum::<f32>();
+                            mesh.triangles.push(if alignment < 0.0 {
+                                [face[0], face[2], face[1]]
+                            } else {
+                                face
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        mesh
+    }
+
+    fn get_normal(
+        mesh: &Mesh,
+        face: [u32; 3],
+    ) -> [f32; 3] {
+        let [a, b, c] = face.map(|index| mesh.vertices[index as usize]);
+        let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ]
+    }
+
+    pub fn write_obj<W: Write>(
+        &self,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        for [x, y, z] in self.vertices.iter() {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in self.triangles.iter() {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_ply<W: Write>(
+        &self,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        writeln!(writer, "property float x")?;
+        writeln!(writer, "property float y")?;
+        writeln!(writer, "property float z")?;
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+        for [x, y, z] in self.vertices.iter() {
+            writeln!(writer, "{} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in self.triangles.iter() {
+            writeln!(writer, "3 {} {} {}", a, b, c)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_obj(
+        &self,
+        file_path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write_obj(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn save_ply(
+        &self,
+        file_path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write_ply(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_from_density_grid_sphere() {
+        let resolution = 24;
+        let radius = 0.6;
+        let densities: Vec<f32> = (0..resolution * resolution * resolution)
+            .map(|index| {
+                let coordinates = [
+                    index / (resolution * resolution),
+                    index / resolution % resolution,
+                    index % resolution,
+                ];
+                let distance = coordinates
+                    .iter()
+                    .map(|&coordinate| {
+                        let value = -1.0
+                            + 2.0 * coordinate as f32 / (resolution - 1) as f32;
+                        value * value
+                    })
+                    .sum::<f32>()
+                    .sqrt();
+                radius - distance
+            })
+            .collect();
+
+        let mesh = Mesh::from_density_grid(
+            &densities,
+            resolution,
+            ([-1.0; 3], [1.0; 3]),
+            0.0,
+        );
+        assert!(!mesh.vertices.is_empty());
+        assert!(!mesh.triangles.is_empty());
+
+        for vertex in mesh.vertices.iter() {
+            let distance =
+                vertex.iter().map(|value| value * value).sum::<f32>().sqrt();
+            assert!((distance - radius).abs() < 0.05, "{}", distance);
+        }
+
+        // The normals point away from the center
+        for face in mesh.triangles.iter() {
+            let normal = Mesh::get_normal(&mesh, *face);
+            let center = mesh.vertices[face[0] as usize];
+            let alignment =
+                (0..3).map(|axis| normal[axis] * center[axis]).sum::<f32>();
+            assert!(alignment >= 0.0);
+        }
+
+        let mut obj = vec![];
+        mesh.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("v ")).count(),
+            mesh.vertices.len()
+        );
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("f ")).count(),
+            mesh.triangles.len()
+        );
+
+        let mut ply = vec![];
+        mesh.write_ply(&mut ply).unwrap();
+        let ply = String::from_utf8(ply).unwrap();
+        assert!(
+            ply.contains(&format!("element vertex {}", mesh.vertices.len()))
+        );
+        assert!(ply.contains(&format!("element face {}", mesh.triangles.len())));
+    }
+
+    #[test]
+    fn mesh_from_density_grid_empty() {
+        let mesh =
+            Mesh::from_density_grid(&[0.0; 8], 2, ([0.0; 3], [1.0; 3]), 0.5);
+        assert_eq!(mesh, Mesh::default());
+
+        let mesh =
+            Mesh::from_density_grid(&[1.0; 7], 2, ([0.0; 3], [1.0; 3]), 0.5);
+        assert_eq!(mesh, Mesh::default());
+    }
+}
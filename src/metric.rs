@@ -1,18 +1,64 @@
-use burn::prelude::*;
-use std::marker::PhantomData;
+use anyhow::Result;
+use burn::{
+    module::Param,
+    nn::{
+        conv::{Conv2d, Conv2dConfig},
+        pool::{MaxPool2d, MaxPool2dConfig},
+        Initializer, PaddingConfig2d,
+    },
+    prelude::*,
+    record,
+    tensor::{activation, module::avg_pool2d},
+};
+use npyz::{npz, NpyFile};
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+    marker::PhantomData,
+    path::PathBuf,
+};
+use zip::ZipArchive;
+
+// NOTE: The feature network is AlexNet, and each of its 5 tapped layers
+// has a linear head weighting the channels as in LPIPS. The module from
+// `init` has random features and heads summing the channels evenly, so
+// it is only a perceptual distance after the pretrained weights are loaded
+// by `init_from_weights`
+#[derive(Debug, Module)]
+pub struct LpipsMetric<B: Backend> {
+    feature_layers: Vec<Conv2d<B>>,
+    linear_layers: Vec<Conv2d<B>>,
+    pool: MaxPool2d,
+    scales: Tensor<B, 4>,
+    shifts: Tensor<B, 4>,
+}
 
 #[derive(Clone, Debug)]
 pub struct PsnrMetric<B: Backend> {
     coefficient: B::FloatElem,
+    offset: B::FloatElem,
     _b: PhantomData<B>,
 }
 
 impl<B: Backend> PsnrMetric<B> {
     pub fn init(device: &B::Device) -> Self {
+        Self::init_with_max_value(device, 1.0)
+    }
+
+    // NOTE: The PSNR is `20 * log10(MAX) - 10 * log10(MSE)`
+    pub fn init_with_max_value(
+        device: &B::Device,
+        max_value: f32,
+    ) -> Self {
         let ten = Tensor::<B, 1>::from_floats([10.0], device);
-        let coefficient = (-ten.clone() / ten.log()).into_scalar();
+        let coefficient = (-ten.clone() / ten.clone().log()).into_scalar();
+        let offset = (Tensor::<B, 1>::from_floats([max_value], device).log()
+            * 20.0
+            / ten.log())
+        .into_scalar();
         Self {
             coefficient,
+            offset,
             _b: PhantomData,
         }
     }
@@ -26,19 +72,243 @@ impl<B: Backend> PsnrMetric<B> {
         self.from_mse((error.clone() * error).mean())
     }
 
+    // NOTE: The errors are reduced over the pixels only,
+    // so there is one PSNR for each channel
+    pub fn forward_per_channel(
+        &self,
+        logits: Tensor<B, 3>,
+        targets: Tensor<B, 3>,
+    ) -> Tensor<B, 1> {
+        let channel_count = logits.dims()[2];
+        let error = (logits - targets).reshape([-1, channel_count as i32]);
+        self.from_mse((error.clone() * error).mean_dim(0).reshape([-1]))
+    }
+
+    // NOTE: Both images are average-pooled by the same factor before comparing
+    pub fn forward_downsampled(
+        &self,
+        logits: Tensor<B, 3>,
+        targets: Tensor<B, 3>,
+        factor: usize,
+    ) -> Tensor<B, 1> {
+        let [height, width, ..] = logits.dims();
+        let factor = factor.clamp(1, height.min(width).max(1));
+        if factor == 1 {
+            return self.forward(logits, targets);
+        }
+
+        let downsample = |image: Tensor<B, 3>| {
+            avg_pool2d(
+                image.permute([2, 0, 1]).unsqueeze::<4>(),
+                [factor, factor],
+                [factor, factor],
+                [0, 0],
+                true,
+            )
+        };
+        self.forward(downsample(logits), downsample(targets))
+    }
+
     pub fn from_mse(
         &self,
         loss: Tensor<B, 1>,
     ) -> Tensor<B, 1> {
-        loss.log() * self.coefficient
+        loss.log() * self.coefficient + self.offset
+    }
+
+    // NOTE: It is the inverse of `from_mse`
+    pub fn psnr_to_mse(
+        &self,
+        psnr: Tensor<B, 1>,
+    ) -> Tensor<B, 1> {
+        ((psnr - self.offset) / self.coefficient).exp()
+    }
+}
+
+impl<B: Backend> LpipsMetric<B> {
+    pub fn init(device: &B::Device) -> Self {
+        let feature_layers = [
+            ([3, 64], 11, 4, 2),
+            ([64, 192], 5, 1, 2),
+            ([192, 384], 3, 1, 1),
+            ([384, 256], 3, 1, 1),
+            ([256, 256], 3, 1, 1),
+        ]
+        .iter()
+        .map(|&(channels, kernel_size, stride, padding)| {
+            Conv2dConfig::new(channels, [kernel_size, kernel_size])
+                .with_stride([stride, stride])
+                .with_padding(PaddingConfig2d::Explicit(padding, padding))
+                .init(device)
+        })
+        .collect::<Vec<_>>();
+        let linear_layers = feature_layers
+            .iter()
+            .map(|layer| {
+                Conv2dConfig::new([layer.weight.dims()[0], 1], [1, 1])
+                    .with_bias(false)
+                    .with_initializer(Initializer::Ones)
+                    .init(device)
+            })
+            .collect();
+
+        Self {
+            feature_layers,
+            linear_layers,
+            pool: MaxPool2dConfig::new([3, 3]).with_strides([2, 2]).init(),
+            scales: Tensor::<B, 1>::from_floats([0.458, 0.448, 0.450], device)
+                .reshape([1, 3, 1, 1]),
+            shifts: Tensor::<B, 1>::from_floats(
+                [-0.030, -0.088, -0.188],
+                device,
+            )
+            .reshape([1, 3, 1, 1]),
+        }
+    }
+
+    // NOTE: The weights are either an NPZ archive converted from the
+    // pretrained LPIPS network by `resources/lpips-alex/convert.py`,
+    // or a record of this module saved with the default recorder
+    pub fn init_from_weights(
+        file_path: impl Into<PathBuf>,
+        device: &B::Device,
+    ) -> Result<Self> {
+        let file_path = file_path.into();
+        if file_path.extension().is_some_and(|extension| extension == "npz") {
+            return Self::init_from_npz(File::open(file_path)?, device);
+        }
+
+        Ok(Self::init(device).load_file(
+            file_path,
+            &record::DefaultRecorder::new(),
+            device,
+        )?)
+    }
+
+    // NOTE: The archive has the following `float32` arrays, where `index`
+    // is the tapped layer in `0..5` and `C` is its output channel count:
+    // - `features.{index}.weight`: `[C, C_in, kernel, kernel]`
+    // - `features.{index}.bias`: `[C]`
+    // - `linears.{index}.weight`: `[1, C, 1, 1]`
+    pub fn init_from_npz<R: Read + Seek>(
+        reader: R,
+        device: &B::Device,
+    ) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut metric = Self::init(device);
+
+        for (index, (layer, linear)) in metric
+            .feature_layers
+            .iter_mut()
+            .zip(&mut metric.linear_layers)
+            .enumerate()
+        {
+            layer.weight = Param::from_tensor(read_array(
+                &mut archive,
+                &format!("features.{}.weight", index),
+                layer.weight.dims(),
+                device,
+            )?);
+            layer.bias = Some(Param::from_tensor(read_array(
+                &mut archive,
+                &format!("features.{}.bias", index),
+                [layer.weight.dims()[0]],
+                device,
+            )?));
+            linear.weight = Param::from_tensor(read_array(
+                &mut archive,
+                &format!("linears.{}.weight", index),
+                linear.weight.dims(),
+                device,
+            )?);
+        }
+
+        Ok(metric)
+    }
+
+    // NOTE: Both images are `[height, width, 3]` in `[0, 1]`, and the distance
+    // is 0 for the identical images. The grayscale images are broadcast to RGB
+    pub fn forward(
+        &self,
+        logits: Tensor<B, 3>,
+        targets: Tensor<B, 3>,
+    ) -> Tensor<B, 1> {
+        let features_logits = self.get_features(logits);
+        let features_targets = self.get_features(targets);
+
+        features_logits
+            .into_iter()
+            .zip(features_targets)
+            .zip(&self.linear_layers)
+            .map(|((features_logits, features_targets), layer)| {
+                let differences =
+                    (features_logits - features_targets).powf_scalar(2.0);
+                layer.forward(differences).mean()
+            })
+            .reduce(|distance, distance_layer| distance + distance_layer)
+            .unwrap()
+    }
+
+    fn get_features(
+        &self,
+        image: Tensor<B, 3>,
+    ) -> Vec<Tensor<B, 4>> {
+        let image = if image.dims()[2] == 1 {
+            image.repeat(2, 3)
+        } else {
+            image
+        };
+        let mut features = (image.permute([2, 0, 1]).unsqueeze::<4>() * 2.0
+            - 1.0
+            - self.shifts.clone())
+            / self.scales.clone();
+
+        self.feature_layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                if index == 1 || index == 2 {
+                    features = self.pool.forward(features.clone());
+                }
+                features = activation::relu(layer.forward(features.clone()));
+                let norms = features.clone().powf_scalar(2.0).sum_dim(1).sqrt();
+                features.clone() / (norms + 1e-10)
+            })
+            .collect()
+    }
+}
+
+fn read_array<B: Backend, R: Read + Seek, const D: usize>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    shape: [usize; D],
+    device: &B::Device,
+) -> Result<Tensor<B, D>> {
+    let array = NpyFile::new(io::BufReader::new(
+        archive.by_name(&npz::file_name_from_array_name(name))?,
+    ))?;
+    if array.shape().iter().map(|&size| size as usize).ne(shape) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The array {:?} is not in the shape {:?}", name, shape),
+        )
+        .into());
     }
+
+    Ok(Tensor::from_data(
+        Data::new(array.into_vec::<f32>()?, Shape::new(shape)).convert(),
+        device,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use burn::tensor::Distribution;
+    use npyz::WriterBuilder;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
 
-    type Backend = burn::backend::Wgpu;
+    type Backend = burn::backend::NdArray;
 
     #[test]
     fn psnr_metric_output() {
@@ -51,7 +321,7 @@ mod tests {
             Tensor::from_floats([[0.5, 0.6, 0.7], [0.0, 0.9, 0.8]], &device);
         let psnr_true = Tensor::<Backend, 1>::from_floats([6.0206003], &device);
         let psnr = metric.forward(logits, targets);
-        assert!(psnr.equal(psnr_true).all().into_scalar());
+        psnr.into_data().assert_approx_eq(&psnr_true.into_data(), 4);
 
         let logits =
             Tensor::from_floats([[0.0, 0.1, 0.2], [0.5, 0.4, 0.3]], &device);
@@ -59,6 +329,248 @@ mod tests {
             Tensor::from_floats([[0.0, 0.6, 0.7], [0.0, 0.4, 0.3]], &device);
         let psnr_true = Tensor::<Backend, 1>::from_floats([9.0309], &device);
         let psnr = metric.forward(logits, targets);
-        assert!(psnr.equal(psnr_true).all().into_scalar());
+        psnr.into_data().assert_approx_eq(&psnr_true.into_data(), 4);
+    }
+
+    #[test]
+    fn psnr_metric_mse_round_trip() {
+        let device = Default::default();
+
+        for max_value in [1.0, 255.0] {
+            let metric =
+                PsnrMetric::<Backend>::init_with_max_value(&device, max_value);
+
+            let psnr = Tensor::<Backend, 1>::from_floats(
+                [6.0206003, 20.0, 48.5],
+                &device,
+            );
+            metric
+                .from_mse(metric.psnr_to_mse(psnr.clone()))
+                .into_data()
+                .assert_approx_eq(&psnr.into_data(), 4);
+
+            let mse = Tensor::<Backend, 1>::from_floats([0.25, 1e-3], &device);
+            metric
+                .psnr_to_mse(metric.from_mse(mse.clone()))
+                .into_data()
+                .assert_approx_eq(&mse.into_data(), 6);
+        }
+    }
+
+    #[test]
+    fn psnr_metric_per_channel_output() {
+        let device = Default::default();
+        let metric = PsnrMetric::<Backend>::init(&device);
+
+        let logits = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.1, 0.2, 0.5, 0.4, 0.3],
+            &device,
+        )
+        .reshape([1, 2, 3]);
+        let targets = Tensor::<Backend, 1>::from_floats(
+            [0.5, 0.1, 0.3, 0.0, 0.4, 0.2],
+            &device,
+        )
+        .reshape([1, 2, 3]);
+        let psnrs = metric.forward_per_channel(logits.clone(), targets.clone());
+        assert_eq!(psnrs.dims(), [3]);
+
+        // The MSEs of the channels are 0.25, 0 and 0.01
+        let [psnr_red, psnr_green, psnr_blue] =
+            psnrs.clone().into_data().value[..]
+        else {
+            unreachable!()
+        };
+        assert!((psnr_red - 6.0206003).abs() < 1e-4);
+        assert_eq!(psnr_green, f32::INFINITY);
+        assert!((psnr_blue - 20.0).abs() < 1e-4);
+
+        // The global MSE is the mean of the channel MSEs
+        let psnr = metric.forward(logits, targets);
+        metric
+            .from_mse(metric.psnr_to_mse(psnrs).mean())
+            .into_data()
+            .assert_approx_eq(&psnr.into_data(), 4);
+    }
+
+    #[test]
+    fn psnr_metric_max_value() {
+        let device = Default::default();
+        let metric = PsnrMetric::<Backend>::init_with_max_value(&device, 255.0);
+
+        // The MSE is 100 and the PSNR is `20 * log10(255) - 20`
+        let logits = Tensor::from_floats([[0.0, 10.0], [20.0, 30.0]], &device);
+        let targets = Tensor::from_floats([[10.0, 0.0], [30.0, 20.0]], &device);
+        let psnr_true = Tensor::<Backend, 1>::from_floats([28.130804], &device);
+        let psnr = metric.forward(logits.clone(), targets.clone());
+        psnr.into_data().assert_approx_eq(&psnr_true.clone().into_data(), 4);
+
+        // The scaled images have the same PSNR as with the unit maximum
+        let psnr = PsnrMetric::<Backend>::init(&device)
+            .forward(logits / 255.0, targets / 255.0);
+        psnr.into_data().assert_approx_eq(&psnr_true.into_data(), 4);
+    }
+
+    #[test]
+    fn lpips_metric_output() {
+        let device = Default::default();
+        let metric = LpipsMetric::<Backend>::init(&device);
+
+        let shape = [32, 32, 3];
+        let image = Tensor::random(shape, Distribution::Default, &device);
+        let image_other = Tensor::random(shape, Distribution::Default, &device);
+
+        let distance = metric.forward(image.clone(), image.clone());
+        assert_eq!(distance.dims(), [1]);
+        assert!(distance.into_scalar().abs() < 1e-6);
+
+        let distance = metric.forward(image.clone(), image_other.clone());
+        assert!(distance.clone().into_scalar() > 0.0);
+
+        // The loaded weights give the same distance
+        let directory = std::env::temp_dir()
+            .join("simple-nerf-tests")
+            .join("lpips_metric_output");
+        std::fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("lpips");
+        metric
+            .clone()
+            .save_file(file_path.clone(), &record::DefaultRecorder::new())
+            .unwrap();
+        let metric_loaded =
+            LpipsMetric::<Backend>::init_from_weights(file_path, &device)
+                .unwrap();
+        metric_loaded
+            .forward(image, image_other)
+            .into_data()
+            .assert_approx_eq(&distance.into_data(), 5);
+    }
+
+    #[test]
+    fn lpips_metric_npz_weights() {
+        let device = Default::default();
+        let metric = LpipsMetric::<Backend>::init(&device);
+
+        let write_weights = |linear_scale: f32, bias_size: Option<usize>| {
+            let mut archive = ZipWriter::new(io::Cursor::new(vec![]));
+            let mut write_array = |name: String, data: Data<f32, 4>| {
+                let shape = data.shape.dims.map(|size| size as u64);
+                let shape = if name.ends_with("bias") {
+                    &shape[3..]
+                } else {
+                    &shape[..]
+                };
+                archive
+                    .start_file(
+                        npz::file_name_from_array_name(&name),
+                        SimpleFileOptions::default()
+                            .compression_method(CompressionMethod::Stored),
+                    )
+                    .unwrap();
+                let mut writer = npyz::WriteOptions::new()
+                    .default_dtype()
+                    .shape(shape)
+                    .writer(&mut archive)
+                    .begin_nd()
+                    .unwrap();
+                writer.extend(data.value).unwrap();
+                writer.finish().unwrap();
+            };
+            for (index, (layer, linear)) in metric
+                .feature_layers
+                .iter()
+                .zip(&metric.linear_layers)
+                .enumerate()
+            {
+                let bias = layer.bias.as_ref().unwrap().val();
+                let bias_size_true = bias.dims()[0];
+                let bias_size = bias_size.unwrap_or(bias_size_true);
+                write_array(
+                    format!("features.{}.weight", index),
+                    layer.weight.val().into_data(),
+                );
+                write_array(
+                    format!("features.{}.bias", index),
+                    bias.reshape([1, 1, 1, bias_size_true])
+                        .slice([0..1, 0..1, 0..1, 0..bias_size])
+                        .into_data(),
+                );
+                write_array(
+                    format!("linears.{}.weight", index),
+                    (linear.weight.val() * linear_scale).into_data(),
+                );
+            }
+            archive.finish().unwrap()
+        };
+
+        let shape = [32, 32, 3];
+        let image = Tensor::random(shape, Distribution::Default, &device);
+        let image_other = Tensor::random(shape, Distribution::Default, &device);
+        let distance = metric.forward(image.clone(), image_other.clone());
+
+        // The features are loaded as is, and the heads weight the channels
+        let metric_loaded = LpipsMetric::<Backend>::init_from_npz(
+            write_weights(2.0, None),
+            &device,
+        )
+        .unwrap();
+        metric_loaded
+            .forward(image, image_other)
+            .into_data()
+            .assert_approx_eq(&(distance * 2.0).into_data(), 4);
+
+        // The arrays in the wrong shapes are rejected
+        let error = LpipsMetric::<Backend>::init_from_npz(
+            write_weights(1.0, Some(1)),
+            &device,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn psnr_metric_downsampled_output() {
+        let device = Default::default();
+        let metric = PsnrMetric::<Backend>::init(&device);
+
+        let get_image = |offset: f32| {
+            let values = (0..16 * 16 * 3)
+                .map(|index| {
+                    let (row, column) = (index / 48, index / 3 % 16);
+                    0.5 + 0.25 * ((row + column) as f32 / 8.0 + offset).sin()
+                })
+                .collect::<Vec<_>>();
+            Tensor::<Backend, 1>::from_floats(values.as_slice(), &device)
+                .reshape([16, 16, 3])
+        };
+
+        // The downsampled PSNR is close to the full PSNR for a smooth image
+        let logits = get_image(0.2);
+        let targets = get_image(0.0);
+        let psnr =
+            metric.forward(logits.clone(), targets.clone()).into_scalar();
+        let psnr_downsampled = metric
+            .forward_downsampled(logits.clone(), targets.clone(), 2)
+            .into_scalar();
+        assert!((psnr - psnr_downsampled).abs() < 0.1);
+
+        // The downsampling is applied to both images consistently
+        let pool = |image: Tensor<Backend, 3>| {
+            image
+                .reshape([8, 2, 8, 2, 3])
+                .mean_dim(3)
+                .mean_dim(1)
+                .reshape([8, 8, 3])
+        };
+        let psnr_true =
+            metric.forward(pool(logits.clone()), pool(targets.clone()));
+        let psnr = metric.forward_downsampled(logits.clone(), targets, 2);
+        psnr.into_data().assert_approx_eq(&psnr_true.into_data(), 4);
+
+        let psnr = metric.forward_downsampled(logits.clone(), logits, 2);
+        assert!(psnr.into_scalar().is_infinite());
     }
 }
@@ -0,0 +1,1706 @@
+pub mod ndc;
+pub mod sampling;
+
+use crate::*;
+use anyhow::{bail, Result};
+use burn::{
+    constant,
+    module::{ModuleMapper, ModuleVisitor, ParamId},
+    prelude::*,
+    record,
+};
+use std::{mem, path::PathBuf};
+
+#[derive(Config, Debug)]
+pub struct VolumeRendererConfig {
+    #[config(default = 0.0)]
+    pub density_noise_std: f32,
+    #[config(default = "None")]
+    pub memory_budget_bytes: Option<usize>,
+    #[config(default = "ColorTransform::Linear")]
+    pub output_transform: ColorTransform,
+    pub scene: scene::VolumetricSceneConfig,
+}
+
+#[derive(Debug, Module)]
+pub struct VolumeRenderer<B: Backend> {
+    density_noise_std: f32,
+    memory_budget_bytes: Option<usize>,
+    output_transform: ColorTransform,
+    scene: scene::VolumetricScene<B>,
+}
+
+#[derive(Clone, Debug)]
+pub struct VolumeRendererOutput<B: Backend> {
+    pub accumulated_opacity: Tensor<B, 3>,
+    pub colors: Tensor<B, 4>,
+    pub image: Tensor<B, 3>,
+    pub opacities: Tensor<B, 4>,
+    pub weights: Tensor<B, 4>,
+}
+
+// NOTE: Every sample is evaluated by the scene once, and the samples are
+// evaluated in `chunk_count` chunks of whole rays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderStats {
+    pub approx_flops: usize,
+    pub chunk_count: usize,
+    pub mlp_evals: usize,
+    pub total_samples: usize,
+}
+
+#[derive(Config, Debug)]
+pub struct DepthConfig {
+    pub near: f32,
+    pub far: f32,
+    #[config(default = 0.0)]
+    pub missed_depth: f32,
+    #[config(default = 0.5)]
+    pub opacity_threshold: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileRect {
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+    pub top: usize,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum ColorTransform {
+    Gamma(f32),
+    Linear,
+    Srgb,
+}
+
+constant!(ColorTransform);
+
+impl VolumeRendererConfig {
+    pub fn init<B: Backend>(
+        &self,
+        device: &B::Device,
+    ) -> Result<VolumeRenderer<B>> {
+        Ok(VolumeRenderer {
+            density_noise_std: self.density_noise_std,
+            memory_budget_bytes: self.memory_budget_bytes,
+            output_transform: self.output_transform,
+            scene: self.scene.init(device)?,
+        })
+    }
+
+    // NOTE: The record is saved by the trainer as `volume-renderer`,
+    // and the config must match the one used in the training
+    pub fn init_from_file_path<B: Backend>(
+        &self,
+        file_path: impl Into<PathBuf>,
+        device: &B::Device,
+    ) -> Result<VolumeRenderer<B>> {
+        Ok(self.init(device)?.load_file(
+            file_path,
+            &record::DefaultRecorder::new(),
+            device,
+        )?)
+    }
+}
+
+impl<B: Backend> VolumeRenderer<B> {
+    pub fn forward(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+    ) -> Tensor<B, 3> {
+        let [height, width, points_per_ray, ..] = directions.dims();
+        let get_rays = |tensor: Tensor<B, 4>, channels| {
+            tensor.reshape([height * width, points_per_ray, channels])
+        };
+
+        self.forward_samples(
+            get_rays(directions, 3),
+            get_rays(intervals, 1),
+            get_rays(positions, 3),
+        )
+        .reshape([height, width, self.scene.get_color_channel_count()])
+    }
+
+    // NOTE: The images are stacked along the rows to share the chunking
+    pub fn forward_batch(
+        &self,
+        directions: Tensor<B, 5>,
+        intervals: Tensor<B, 5>,
+        positions: Tensor<B, 5>,
+    ) -> Tensor<B, 4> {
+        let [image_count, height, width, points_per_ray, ..] =
+            directions.dims();
+        let get_stacked = |tensor: Tensor<B, 5>, channels| {
+            tensor.reshape([
+                image_count * height,
+                width,
+                points_per_ray,
+                channels,
+            ])
+        };
+
+        self.forward(
+            get_stacked(directions, 3),
+            get_stacked(intervals, 1),
+            get_stacked(positions, 3),
+        )
+        .reshape([
+            image_count,
+            height,
+            width,
+            self.scene.get_color_channel_count(),
+        ])
+    }
+
+    // NOTE: The rays are sampled at the distances, where the intervals have
+    // the same sentinel as in the dataset
+    pub fn forward_rays(
+        &self,
+        origins: Tensor<B, 2>,
+        directions: Tensor<B, 2>,
+        distances: Tensor<B, 2>,
+    ) -> Tensor<B, 2> {
+        let [ray_count, points_per_ray] = distances.dims();
+        let shape = [ray_count, points_per_ray, 3];
+
+        let intervals = sampling::get_intervals(distances.clone()).reshape([
+            ray_count,
+            points_per_ray,
+            1,
+        ]);
+        let directions = directions.reshape([ray_count, 1, 3]).expand(shape);
+        let positions = origins.reshape([ray_count, 1, 3]).expand(shape)
+            + directions.clone()
+                * distances.reshape([ray_count, points_per_ray, 1]);
+
+        self.forward_samples(directions, intervals, positions)
+    }
+
+    // NOTE: The samples are `[N, P, C]` for `N` rays, and both the image and
+    // the ray paths are rendered here as a single-column image
+    fn forward_samples(
+        &self,
+        directions: Tensor<B, 3>,
+        intervals: Tensor<B, 3>,
+        positions: Tensor<B, 3>,
+    ) -> Tensor<B, 2> {
+        let [ray_count, points_per_ray, ..] = directions.dims();
+        let get_column = |tensor: Tensor<B, 3>, channels| {
+            tensor.reshape([ray_count, 1, points_per_ray, channels])
+        };
+
+        self.forward_with_aux(
+            get_column(directions, 3),
+            get_column(intervals, 1),
+            get_column(positions, 3),
+        )
+        .image
+        .reshape([ray_count, self.scene.get_color_channel_count()])
+    }
+
+    pub fn forward_with_aux(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+    ) -> VolumeRendererOutput<B> {
+        self.render(directions, intervals, positions, |outputs| outputs)
+    }
+
+    pub fn forward_tiled(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        tile_size: usize,
+        tile_overlap: usize,
+    ) -> Tensor<B, 3> {
+        let [height, width, ..] = directions.dims();
+        let device = directions.device();
+        let channel_count = self.scene.get_color_channel_count();
+
+        self.forward_tiles(
+            directions,
+            intervals,
+            positions,
+            tile_size,
+            tile_overlap,
+        )
+        .fold(
+            Tensor::zeros([height, width, channel_count], &device),
+            |image, (rect, tile)| {
+                image.slice_assign(
+                    [
+                        rect.top..rect.bottom,
+                        rect.left..rect.right,
+                        0..channel_count,
+                    ],
+                    tile,
+                )
+            },
+        )
+    }
+
+    // NOTE: Each tile is rendered with the overlapping border for context,
+    // and only its valid center is yielded in the row-major order
+    pub fn forward_tiles(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        tile_size: usize,
+        tile_overlap: usize,
+    ) -> impl Iterator<Item = (TileRect, Tensor<B, 3>)> + '_ {
+        let [height, width, points_per_ray, ..] = directions.dims();
+        let tile_size = tile_size.max(1);
+
+        let rects = (0..height)
+            .step_by(tile_size)
+            .flat_map(|top| {
+                (0..width).step_by(tile_size).map(move |left| TileRect {
+                    bottom: (top + tile_size).min(height),
+                    left,
+                    right: (left + tile_size).min(width),
+                    top,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        rects.into_iter().map(move |rect| {
+            let padded_top = rect.top.saturating_sub(tile_overlap);
+            let padded_left = rect.left.saturating_sub(tile_overlap);
+            let padded_bottom = (rect.bottom + tile_overlap).min(height);
+            let padded_right = (rect.right + tile_overlap).min(width);
+
+            let get_tile = |tensor: Tensor<B, 4>, channels| {
+                tensor.slice([
+                    padded_top..padded_bottom,
+                    padded_left..padded_right,
+                    0..points_per_ray,
+                    0..channels,
+                ])
+            };
+            let tile = self.forward(
+                get_tile(directions.clone(), 3),
+                get_tile(intervals.clone(), 1),
+                get_tile(positions.clone(), 3),
+            );
+            let channel_count = tile.dims()[2];
+
+            (
+                rect,
+                tile.slice([
+                    rect.top - padded_top..rect.bottom - padded_top,
+                    rect.left - padded_left..rect.right - padded_left,
+                    0..channel_count,
+                ]),
+            )
+        })
+    }
+
+    // NOTE: The samples outside `[near, far]` are fully translucent,
+    // so they neither contribute to nor occlude the other samples
+    pub fn forward_with_bounds(
+        &self,
+        directions: Tensor<B, 4>,
+        distances: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        near: Tensor<B, 3>,
+        far: Tensor<B, 3>,
+    ) -> Tensor<B, 3> {
+        let shape = distances.shape();
+        let outsides = distances
+            .clone()
+            .lower(near.unsqueeze_dim::<4>(2).expand(shape.clone()))
+            .int()
+            + distances.greater(far.unsqueeze_dim::<4>(2).expand(shape)).int();
+        let outsides = outsides.greater_elem(0);
+
+        self.forward_with_hook(directions, intervals, positions, |outputs| {
+            scene::VolumetricSceneOutput {
+                densities: outputs.densities.mask_fill(outsides.clone(), 0.0),
+                ..outputs
+            }
+        })
+    }
+
+    // NOTE: The rays outside the mask are the background without evaluating
+    // the scene, where the background is black as in the compositing
+    pub fn forward_with_mask(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        mask: Tensor<B, 2, Bool>,
+    ) -> Tensor<B, 3> {
+        let [height, width, points_per_ray, ..] = directions.dims();
+        let channel_count = self.scene.get_color_channel_count();
+        let device = directions.device();
+
+        let background = self
+            .output_transform
+            .forward(Tensor::zeros([height * width, channel_count], &device));
+        let indices = mask
+            .clone()
+            .reshape([-1])
+            .int()
+            .into_data()
+            .convert::<i64>()
+            .value
+            .into_iter()
+            .enumerate()
+            .filter(|(_, is_masked)| *is_masked != 0)
+            .map(|(index, _)| index as i64)
+            .collect::<Vec<_>>();
+        let ray_count = indices.len();
+        if ray_count == 0 {
+            return background.reshape([height, width, channel_count]);
+        }
+
+        let indices = Tensor::<B, 1, Int>::from_data(
+            Data::new(indices, Shape::new([ray_count])).convert(),
+            &device,
+        );
+        let get_rays = |tensor: Tensor<B, 4>, channels| {
+            tensor
+                .reshape([height * width, points_per_ray, channels])
+                .select(0, indices.clone())
+                .reshape([ray_count, 1, points_per_ray, channels])
+        };
+        let colors = self
+            .forward(
+                get_rays(directions, 3),
+                get_rays(intervals, 1),
+                get_rays(positions, 3),
+            )
+            .reshape([ray_count, channel_count]);
+
+        let image = Tensor::zeros([height * width, channel_count], &device)
+            .select_assign(0, indices, colors);
+        let mask = mask
+            .reshape([height * width, 1])
+            .expand([height * width, channel_count]);
+        background.mask_where(mask, image).reshape([
+            height,
+            width,
+            channel_count,
+        ])
+    }
+
+    pub fn forward_with_hook(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        hook: impl Fn(
+            scene::VolumetricSceneOutput<B, 4>,
+        ) -> scene::VolumetricSceneOutput<B, 4>,
+    ) -> Tensor<B, 3> {
+        self.render(directions, intervals, positions, hook).image
+    }
+
+    // NOTE: The throwaway pass triggers the pipeline compilation of the
+    // backend, such as the Wgpu shaders, before rendering the timed frames
+    pub fn warmup(
+        &self,
+        [height, width, points_per_ray]: [usize; 3],
+        device: &B::Device,
+    ) {
+        self.forward(
+            Tensor::zeros([height, width, points_per_ray, 3], device),
+            Tensor::ones([height, width, points_per_ray, 1], device),
+            Tensor::zeros([height, width, points_per_ray, 3], device),
+        )
+        .into_data();
+    }
+
+    // NOTE: The colors and the densities of the scene are in the shape of
+    // the samples, and they are not composited along the rays
+    pub fn forward_raw(
+        &self,
+        directions: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+    ) -> scene::VolumetricSceneOutput<B, 4> {
+        self.get_scene_outputs(directions, positions)
+    }
+
+    fn render(
+        &self,
+        directions: Tensor<B, 4>,
+        intervals: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+        hook: impl Fn(
+            scene::VolumetricSceneOutput<B, 4>,
+        ) -> scene::VolumetricSceneOutput<B, 4>,
+    ) -> VolumeRendererOutput<B> {
+        let [height, width, points_per_ray, ..] = directions.dims();
+        let scene_outputs = self.get_scene_outputs(directions, positions);
+
+        // NOTE: The hook maps the colors and densities before compositing
+        let scene::VolumetricSceneOutput {
+            colors,
+            densities,
+        } = hook(scene_outputs);
+
+        {
+            let translucency = (-densities * intervals).exp();
+
+            let cumulative_translucency = {
+                let mut cumulative_product = translucency.clone() + 1e-6;
+
+                // NOTE: This is a naive implementation of cumulative product
+                for index in 1..points_per_ray {
+                    let product = cumulative_product.clone().slice([
+                        0..height,
+                        0..width,
+                        index - 1..index,
+                    ]) * cumulative_product.clone().slice([
+                        0..height,
+                        0..width,
+                        index..index + 1,
+                    ]);
+
+                    cumulative_product = cumulative_product.slice_assign(
+                        [0..height, 0..width, index..index + 1],
+                        product,
+                    );
+                }
+
+                cumulative_product
+            };
+
+            let opacities = -translucency + 1.0;
+            let transmittance = opacities.clone() * cumulative_translucency;
+
+            VolumeRendererOutput {
+                accumulated_opacity: transmittance
+                    .clone()
+                    .sum_dim(2)
+                    .squeeze::<3>(2),
+                colors: colors.clone(),
+                image: self.output_transform.forward(
+                    (colors * transmittance.clone()).sum_dim(2).squeeze::<3>(2),
+                ),
+                opacities,
+                weights: transmittance,
+            }
+        }
+    }
+
+    fn get_scene_outputs(
+        &self,
+        directions: Tensor<B, 4>,
+        positions: Tensor<B, 4>,
+    ) -> scene::VolumetricSceneOutput<B, 4> {
+        let [height, width, points_per_ray, ..] = directions.dims();
+
+        #[cfg(test)]
+        tests::SCENE_POINT_COUNT.with(|count| {
+            count.set(count.get() + height * width * points_per_ray)
+        });
+
+        // NOTE: The density noise only regularizes the training path
+        let density_noise_std = if B::ad_enabled() {
+            self.density_noise_std
+        } else {
+            0.0
+        };
+
+        let chunk_count = self.get_chunk_count(height * width, points_per_ray);
+
+        let directions_chunks =
+            directions.reshape([-1, 3]).chunk(chunk_count, 0);
+        let positions_chunks = positions.reshape([-1, 3]).chunk(chunk_count, 0);
+
+        let (colors, densities): (Vec<_>, Vec<_>) = directions_chunks
+            .into_iter()
+            .zip(positions_chunks)
+            .map(|(directions, positions)| {
+                let outputs = self.scene.forward_with_density_noise(
+                    directions,
+                    positions,
+                    density_noise_std,
+                );
+                (outputs.colors, outputs.densities)
+            })
+            .unzip();
+
+        scene::VolumetricSceneOutput {
+            colors: Tensor::cat(colors, 0).reshape([
+                height,
+                width,
+                points_per_ray,
+                self.scene.get_color_channel_count(),
+            ]),
+            densities: Tensor::cat(densities, 0).reshape([
+                height,
+                width,
+                points_per_ray,
+                1,
+            ]),
+        }
+    }
+
+    // NOTE: The chunk count is the fewest chunks of whole rays whose estimated
+    // activations fit in the budget, and it is reported by `render_stats`
+    // to be logged once by the callers instead of every render
+    fn get_chunk_count(
+        &self,
+        ray_count: usize,
+        points_per_ray: usize,
+    ) -> usize {
+        // NOTE: Using hardset chunk count to be acceptible for Wgpu backend with Metal device,
+        // which is only a memory heuristic and does not affect the results on other backends
+        let memory_budget_bytes = match self.memory_budget_bytes {
+            Some(memory_budget_bytes) => memory_budget_bytes,
+            None => return 4,
+        };
+
+        let ray_bytes = points_per_ray
+            * self.scene.get_activation_size()
+            * mem::size_of::<B::FloatElem>();
+        (ray_count * ray_bytes)
+            .div_ceil(memory_budget_bytes.max(1))
+            .clamp(1, ray_count.max(1))
+    }
+
+    pub fn memory_budget_bytes(&self) -> Option<usize> {
+        self.memory_budget_bytes
+    }
+
+    // NOTE: The encoder frequencies are constant buffers, so only the weights
+    // and the biases of the linear layers are counted
+    pub fn num_parameters(&self) -> usize {
+        self.num_params()
+    }
+
+    pub fn summary(&self) -> String {
+        self.scene.summary()
+    }
+
+    pub fn render_stats(
+        &self,
+        image_shape: [usize; 2],
+        points_per_ray: usize,
+    ) -> RenderStats {
+        let [height, width] = image_shape;
+        let total_samples = height * width * points_per_ray;
+        RenderStats {
+            approx_flops: total_samples * self.scene.get_flop_count(),
+            chunk_count: self.get_chunk_count(height * width, points_per_ray),
+            mlp_evals: total_samples,
+            total_samples,
+        }
+    }
+
+    pub fn lerp(
+        a: &Self,
+        b: &Self,
+        t: f64,
+    ) -> Result<Self> {
+        let parameters_a = ParametersCollector::collect(a);
+        let parameters_b = ParametersCollector::collect(b);
+        if parameters_a.shapes != parameters_b.shapes {
+            bail!("Renderers must have identical architectures to interpolate");
+        }
+
+        Ok(a.clone().map(&mut ParametersInterpolator {
+            others: parameters_b.parameters.into_iter(),
+            t,
+        }))
+    }
+
+    pub fn extract_mesh(
+        &self,
+        resolution: usize,
+        bounds: ([f32; 3], [f32; 3]),
+        density_threshold: f32,
+    ) -> mesh::Mesh {
+        let device = self.devices().into_iter().next().unwrap_or_default();
+        let (lower, upper) = bounds;
+
+        let positions = (0..resolution.pow(3))
+            .flat_map(|index| {
+                let coordinates = [
+                    index / (resolution * resolution),
+                    index / resolution % resolution,
+                    index % resolution,
+                ];
+                (0..3).map(move |axis| {
+                    lower[axis]
+                        + (upper[axis] - lower[axis]) * coordinates[axis] as f32
+                            / (resolution.max(2) - 1) as f32
+                })
+            })
+            .collect::<Vec<f32>>();
+
+        // NOTE: Evaluating the grid in chunks to bound the device memory
+        let chunk_size = 1 << 16;
+        let densities = positions
+            .chunks(chunk_size * 3)
+            .flat_map(|positions| {
+                let size = positions.len() / 3;
+                let positions = Tensor::<B, 2>::from_data(
+                    Data::new(positions.to_vec(), Shape::new([size, 3]))
+                        .convert(),
+                    &device,
+                );
+                self.scene
+                    .forward_density(positions)
+                    .into_data()
+                    .convert::<f32>()
+                    .value
+            })
+            .collect::<Vec<f32>>();
+
+        mesh::Mesh::from_density_grid(
+            &densities,
+            resolution,
+            bounds,
+            density_threshold,
+        )
+    }
+}
+
+impl<B: Backend> VolumeRendererOutput<B> {
+    // NOTE: The minimum, mean and maximum of the opacities of the samples,
+    // excluding the last sample of each ray, whose sentinel interval would
+    // pin the maximum at 1 for any positive density
+    pub fn get_opacity_stats(&self) -> [f32; 3] {
+        let [height, width, points_per_ray, _] = self.opacities.dims();
+        let opacities = self.opacities.clone().slice([
+            0..height,
+            0..width,
+            0..points_per_ray.saturating_sub(1),
+            0..1,
+        ]);
+        [
+            opacities.clone().min().into_scalar().elem(),
+            opacities.clone().mean().into_scalar().elem(),
+            opacities.max().into_scalar().elem(),
+        ]
+    }
+
+    // NOTE: The uncertainty is the variance of the sample colors weighted by
+    // the normalized weights, and averaged over the color channels
+    pub fn get_uncertainty(&self) -> Tensor<B, 3> {
+        let opacity = self.weights.clone().sum_dim(2).clamp_min(1e-10);
+        let means = (self.colors.clone() * self.weights.clone()).sum_dim(2)
+            / opacity.clone();
+        let deviations = self.colors.clone() - means;
+
+        ((deviations.clone() * deviations * self.weights.clone()).sum_dim(2)
+            / opacity)
+            .mean_dim(3)
+            .squeeze::<3>(2)
+    }
+
+    // NOTE: The depth is the expected distance of the opaque rays clamped to
+    // `[near, far]`, and the rays missing geometry have `missed_depth`
+    pub fn get_depth(
+        &self,
+        distances: Tensor<B, 4>,
+        config: &DepthConfig,
+    ) -> Tensor<B, 3> {
+        let opacity = self.accumulated_opacity.clone();
+        let depth =
+            (self.weights.clone() * distances).sum_dim(2).squeeze::<3>(2)
+                / opacity.clone().clamp_min(1e-10);
+        let depth = depth.clamp(config.near, config.far);
+
+        depth.mask_fill(
+            opacity.lower_elem(config.opacity_threshold),
+            config.missed_depth,
+        )
+    }
+}
+
+impl ColorTransform {
+    pub fn forward<B: Backend, const D: usize>(
+        &self,
+        tensor: Tensor<B, D>,
+    ) -> Tensor<B, D> {
+        // NOTE: The bases of the fractional powers are clamped
+        // to keep the gradients finite
+        match self {
+            ColorTransform::Gamma(gamma) => {
+                tensor.clamp_min(1e-10).powf_scalar(1.0 / gamma)
+            },
+            ColorTransform::Linear => tensor,
+            ColorTransform::Srgb => {
+                let is_linear = tensor.clone().lower_equal_elem(0.0031308);
+                (tensor.clone().clamp_min(0.0031308).powf_scalar(1.0 / 2.4)
+                    * 1.055
+                    - 0.055)
+                    .mask_where(is_linear, tensor * 12.92)
+            },
+        }
+    }
+
+    pub fn inverse<B: Backend, const D: usize>(
+        &self,
+        tensor: Tensor<B, D>,
+    ) -> Tensor<B, D> {
+        match self {
+            ColorTransform::Gamma(gamma) => {
+                tensor.clamp_min(0.0).powf_scalar(*gamma)
+            },
+            ColorTransform::Linear => tensor,
+            ColorTransform::Srgb => {
+                let is_linear = tensor.clone().lower_equal_elem(0.04045);
+                ((tensor.clone().clamp_min(0.04045) + 0.055) / 1.055)
+                    .powf_scalar(2.4)
+                    .mask_where(is_linear, tensor / 12.92)
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ParametersCollector<B: Backend> {
+    parameters: Vec<Tensor<B, 1>>,
+    shapes: Vec<Vec<usize>>,
+}
+
+impl<B: Backend> ParametersCollector<B> {
+    fn collect<M: Module<B>>(module: &M) -> Self {
+        let mut collector = Self {
+            parameters: vec![],
+            shapes: vec![],
+        };
+        module.visit(&mut collector);
+        collector
+    }
+}
+
+impl<B: Backend> ModuleVisitor<B> for ParametersCollector<B> {
+    fn visit_float<const D: usize>(
+        &mut self,
+        _id: &ParamId,
+        tensor: &Tensor<B, D>,
+    ) {
+        self.shapes.push(tensor.dims().to_vec());
+        self.parameters.push(tensor.clone().reshape([-1]));
+    }
+}
+
+#[derive(Debug)]
+struct ParametersInterpolator<B: Backend> {
+    others: std::vec::IntoIter<Tensor<B, 1>>,
+    t: f64,
+}
+
+impl<B: Backend> ModuleMapper<B> for ParametersInterpolator<B> {
+    fn map_float<const D: usize>(
+        &mut self,
+        _id: &ParamId,
+        tensor: Tensor<B, D>,
+    ) -> Tensor<B, D> {
+        match self.others.next() {
+            Some(other) => {
+                let other = other.reshape(tensor.shape());
+                tensor * (1.0 - self.t) + other * self.t
+            },
+            None => tensor,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use burn::{module::AutodiffModule, tensor::Distribution};
+    use std::cell::Cell;
+
+    type Backend = burn::backend::NdArray;
+
+    thread_local! {
+        pub(crate) static SCENE_POINT_COUNT: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn volume_renderer_output_shape() {
+        let device = Default::default();
+
+        let points_per_ray = 16;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(3, 8))
+                .init::<Backend>(&device);
+        assert!(renderer.is_ok(), "Error: {}", renderer.unwrap_err());
+
+        let renderer = renderer.unwrap();
+        let directions = Tensor::random(
+            [125, 100, points_per_ray, 3],
+            Distribution::Default,
+            &device,
+        );
+        let distances = Tensor::arange(0..points_per_ray as i64, &device)
+            .reshape([1, 1, points_per_ray, 1])
+            .expand([125, 100, points_per_ray, 1])
+            .float()
+            + Tensor::random(
+                [125, 100, points_per_ray, 1],
+                Distribution::Default,
+                &device,
+            );
+        let positions = Tensor::random(
+            [125, 100, points_per_ray, 3],
+            Distribution::Default,
+            &device,
+        );
+
+        let outputs = renderer.forward_with_aux(
+            directions.clone(),
+            distances.clone(),
+            positions.clone(),
+        );
+        assert_eq!(outputs.image.dims(), [125, 100, 3]);
+        assert_eq!(outputs.weights.dims(), [125, 100, points_per_ray, 1]);
+
+        let outputs = renderer.forward(directions, distances, positions);
+        assert_eq!(outputs.dims(), [125, 100, 3]);
+    }
+
+    #[test]
+    fn volume_renderer_raw_output() {
+        let device = Default::default();
+
+        let points_per_ray = 6;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+        let directions = Tensor::random(
+            [5, 4, points_per_ray, 3],
+            Distribution::Default,
+            &device,
+        );
+        let intervals = Tensor::ones([5, 4, points_per_ray, 1], &device);
+        let positions = Tensor::random(
+            [5, 4, points_per_ray, 3],
+            Distribution::Default,
+            &device,
+        );
+
+        let outputs_raw =
+            renderer.forward_raw(directions.clone(), positions.clone());
+        assert_eq!(outputs_raw.colors.dims(), [5, 4, points_per_ray, 3]);
+        assert_eq!(outputs_raw.densities.dims(), [5, 4, points_per_ray, 1]);
+
+        let outputs =
+            renderer.forward_with_aux(directions, intervals, positions);
+        outputs_raw
+            .colors
+            .into_data()
+            .assert_approx_eq(&outputs.colors.into_data(), 5);
+        (-(-outputs_raw.densities).exp() + 1.0)
+            .into_data()
+            .assert_approx_eq(&outputs.opacities.into_data(), 5);
+    }
+
+    #[test]
+    fn volume_renderer_warmup() {
+        let device = Default::default();
+
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        SCENE_POINT_COUNT.with(|count| count.set(0));
+        renderer.warmup([2, 3, 4], &device);
+        assert_eq!(SCENE_POINT_COUNT.with(|count| count.get()), 2 * 3 * 4);
+    }
+
+    #[test]
+    fn volume_renderer_memory_budget() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let config =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8));
+        let renderer = config.init::<Backend>(&device).unwrap();
+        let renderer_budgeted = VolumeRenderer {
+            memory_budget_bytes: Some(1),
+            ..renderer.clone()
+        };
+        let renderer_unbounded = VolumeRenderer {
+            memory_budget_bytes: Some(usize::MAX),
+            ..renderer.clone()
+        };
+
+        assert_eq!(renderer.memory_budget_bytes(), None);
+        assert_eq!(renderer_budgeted.memory_budget_bytes(), Some(1));
+
+        // The tiny budget renders one ray per chunk
+        assert_eq!(
+            renderer.render_stats([3, 2], points_per_ray).chunk_count,
+            4
+        );
+        assert_eq!(
+            renderer_budgeted.render_stats([3, 2], points_per_ray).chunk_count,
+            6
+        );
+        assert_eq!(
+            renderer_unbounded.render_stats([3, 2], points_per_ray).chunk_count,
+            1
+        );
+
+        let shape = [3, 2, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals = Tensor::random(
+            [3, 2, points_per_ray, 1],
+            Distribution::Default,
+            &device,
+        );
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+
+        let image = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        let image_budgeted =
+            renderer_budgeted.forward(directions, intervals, positions);
+        image_budgeted.into_data().assert_approx_eq(&image.into_data(), 5);
+    }
+
+    #[test]
+    fn volume_renderer_summary() {
+        let device = Default::default();
+
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(10, 256))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        assert_eq!(renderer.num_parameters(), 526340);
+
+        let summary = renderer.summary();
+        let lines = summary.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "direction_encoder: 3 -> 63");
+        assert_eq!(lines[2], "hidden_layers.0: 126 -> 256");
+        assert_eq!(lines[7], "hidden_layers.5: 382 -> 256");
+        assert_eq!(lines[10], "output_layer: 256 -> 4");
+        assert_eq!(lines[11], "parameters: 526340");
+    }
+
+    #[test]
+    fn volume_renderer_render_stats() {
+        let device = Default::default();
+
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(10, 256))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let stats = renderer.render_stats([100, 125], 20);
+        assert_eq!(stats.total_samples, 100 * 125 * 20);
+        assert_eq!(stats.mlp_evals, stats.total_samples);
+
+        // The weights are the parameters without the biases
+        let weight_count = 526340 - (256 * 8 + 4);
+        assert_eq!(stats.approx_flops, stats.total_samples * 2 * weight_count);
+    }
+
+    #[test]
+    fn volume_renderer_batch() {
+        let device = Default::default();
+
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [3, 5, 4, 6, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals =
+            Tensor::random([3, 5, 4, 6, 1], Distribution::Default, &device);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+
+        let images = renderer.forward_batch(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert_eq!(images.dims(), [3, 5, 4, 3]);
+
+        // Each image agrees with the one rendered alone
+        let get_view = |tensor: Tensor<Backend, 5>, channels| {
+            tensor.slice([1..2, 0..5, 0..4, 0..6, 0..channels]).squeeze(0)
+        };
+        let image = renderer.forward(
+            get_view(directions, 3),
+            get_view(intervals, 1),
+            get_view(positions, 3),
+        );
+        images
+            .slice([1..2, 0..5, 0..4, 0..3])
+            .squeeze::<3>(0)
+            .into_data()
+            .assert_approx_eq(&image.into_data(), 5);
+    }
+
+    #[test]
+    fn volume_renderer_saved_and_loaded() {
+        let device = Default::default();
+
+        let config =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8));
+        let renderer = config.init::<Backend>(&device).unwrap();
+
+        let directory = std::env::temp_dir()
+            .join("simple-nerf-tests")
+            .join("volume_renderer_saved_and_loaded");
+        std::fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("volume-renderer");
+        renderer
+            .clone()
+            .save_file(file_path.clone(), &record::DefaultRecorder::new())
+            .unwrap();
+
+        let renderer_loaded =
+            config.init_from_file_path::<Backend>(file_path, &device).unwrap();
+
+        let shape = [3, 4, 5, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals =
+            Tensor::random([3, 4, 5, 1], Distribution::Default, &device);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let render = |renderer: &VolumeRenderer<Backend>| {
+            renderer
+                .forward(
+                    directions.clone(),
+                    intervals.clone(),
+                    positions.clone(),
+                )
+                .into_data()
+        };
+        assert_eq!(render(&renderer_loaded), render(&renderer));
+
+        assert!(config
+            .init_from_file_path::<Backend>(directory.join("missing"), &device)
+            .is_err());
+    }
+
+    #[test]
+    fn volume_renderer_rays() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let origins = Tensor::<Backend, 2>::from_floats(
+            [[0.0, 0.0, 4.0], [0.5, -0.5, 4.0]],
+            &device,
+        );
+        let directions = Tensor::<Backend, 2>::from_floats(
+            [[0.0, 0.0, -1.0], [0.1, 0.2, -1.0]],
+            &device,
+        );
+        let distances = Tensor::<Backend, 2>::from_floats(
+            [[2.0, 3.0, 4.0, 5.0], [2.5, 3.0, 4.5, 5.5]],
+            &device,
+        );
+        let colors = renderer.forward_rays(
+            origins.clone(),
+            directions.clone(),
+            distances.clone(),
+        );
+        assert_eq!(colors.dims(), [2, 3]);
+
+        // The single ray agrees with the image path
+        let origin = origins.slice([1..2, 0..3]).reshape([1, 1, 1, 3]);
+        let direction = directions.slice([1..2, 0..3]).reshape([1, 1, 1, 3]);
+        let image = renderer.forward(
+            direction.clone().expand([1, 1, points_per_ray, 3]),
+            Tensor::<Backend, 1>::from_floats([0.5, 1.5, 1.0, 1e9], &device)
+                .reshape([1, 1, points_per_ray, 1]),
+            origin
+                + direction
+                    * Tensor::<Backend, 1>::from_floats(
+                        [2.5, 3.0, 4.5, 5.5],
+                        &device,
+                    )
+                    .reshape([1, 1, points_per_ray, 1]),
+        );
+        image.into_data().assert_approx_eq(
+            &colors.slice([1..2, 0..3]).reshape([1, 1, 3]).into_data(),
+            6,
+        );
+    }
+
+    #[test]
+    fn volume_renderer_scene_output_hook() {
+        let device = Default::default();
+
+        let points_per_ray = 8;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [10, 12, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals = Tensor::random(
+            [10, 12, points_per_ray, 1],
+            Distribution::Default,
+            &device,
+        );
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+
+        let outputs = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        let outputs_identity = renderer.forward_with_hook(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+            |outputs| outputs,
+        );
+        assert!(outputs.equal(outputs_identity).all().into_scalar());
+
+        // Clamping the densities to zero makes every ray fully translucent
+        let outputs_clamped = renderer.forward_with_hook(
+            directions,
+            intervals,
+            positions,
+            |outputs| scene::VolumetricSceneOutput {
+                densities: outputs.densities.clamp(0.0, 0.0),
+                ..outputs
+            },
+        );
+        assert_eq!(outputs_clamped.clone().max().into_scalar(), 0.0);
+        assert_eq!(outputs_clamped.min().into_scalar(), 0.0);
+    }
+
+    #[test]
+    fn volume_renderer_scene_output_split() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [3, 2, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals = Tensor::ones([3, 2, points_per_ray, 1], &device);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+
+        // The colors are composited and the densities only weight them
+        let colors =
+            Tensor::<Backend, 1>::from_floats([0.2, 0.4, 0.8], &device);
+        let outputs =
+            renderer.render(directions, intervals, positions, |outputs| {
+                scene::VolumetricSceneOutput {
+                    colors: colors.clone().reshape([1, 1, 1, 3]).expand([
+                        3,
+                        2,
+                        points_per_ray,
+                        3,
+                    ]),
+                    densities: outputs.densities.ones_like(),
+                }
+            });
+        let image_true = colors.reshape([1, 1, 3]).expand([3, 2, 3])
+            * outputs.accumulated_opacity.clone();
+        outputs.image.into_data().assert_approx_eq(&image_true.into_data(), 5);
+        assert!(outputs
+            .accumulated_opacity
+            .greater_elem(0.1)
+            .all()
+            .into_scalar());
+    }
+
+    #[test]
+    fn volume_renderer_bounds() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer = VolumeRendererConfig::new(
+            scene::tests::get_test_config(2, 8).with_density_activation(
+                scene::Activation::Softplus {
+                    beta: 1.0,
+                    shift: 0.0,
+                },
+            ),
+        )
+        .init::<Backend>(&device)
+        .unwrap();
+
+        let shape = [3, 2, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let distances =
+            Tensor::<Backend, 1>::from_floats([2.0, 3.0, 4.0, 5.0], &device)
+                .reshape([1, 1, points_per_ray, 1])
+                .expand([3, 2, points_per_ray, 1]);
+        let intervals =
+            Tensor::<Backend, 1>::from_floats([1.0, 1.0, 1.0, 1e9], &device)
+                .reshape([1, 1, points_per_ray, 1])
+                .expand([3, 2, points_per_ray, 1]);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let render = |near, far| {
+            renderer.forward_with_bounds(
+                directions.clone(),
+                distances.clone(),
+                intervals.clone(),
+                positions.clone(),
+                Tensor::full([3, 2, 1], near, &device),
+                Tensor::full([3, 2, 1], far, &device),
+            )
+        };
+
+        // The bounds covering every sample change nothing
+        let image = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        assert!(image.clone().greater_elem(0.0).all().into_scalar());
+        assert!(render(2.0, 5.0).equal(image.clone()).all().into_scalar());
+
+        // The far bound below every sample gives the background color
+        assert_eq!(render(0.0, 1.0).abs().max().into_scalar(), 0.0);
+
+        // The bounds excluding some samples change the output
+        assert!(!render(3.5, 5.0).equal(image).all().into_scalar());
+    }
+
+    #[test]
+    fn volume_renderer_ray_mask() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [3, 2, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals =
+            Tensor::<Backend, 1>::from_floats([1.0, 1.0, 1.0, 1e9], &device)
+                .reshape([1, 1, points_per_ray, 1])
+                .expand([3, 2, points_per_ray, 1]);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let render = |mask: [[bool; 2]; 3]| {
+            SCENE_POINT_COUNT.with(|count| count.set(0));
+            let image = renderer.forward_with_mask(
+                directions.clone(),
+                intervals.clone(),
+                positions.clone(),
+                Tensor::from_bool(Data::from(mask), &device),
+            );
+            (image, SCENE_POINT_COUNT.with(|count| count.get()))
+        };
+
+        // The fully masked image is the background without the scene
+        let (image, point_count) = render([[false; 2]; 3]);
+        assert_eq!(image.dims(), [3, 2, 3]);
+        assert_eq!(image.abs().max().into_scalar(), 0.0);
+        assert_eq!(point_count, 0);
+
+        // The unmasked image is the same as the full rendering
+        let image_true = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        let (image, point_count) = render([[true; 2]; 3]);
+        image.into_data().assert_approx_eq(&image_true.clone().into_data(), 5);
+        assert_eq!(point_count, 6 * points_per_ray);
+
+        // Only the unmasked rays are evaluated
+        let (image, point_count) =
+            render([[true, false], [false, false], [false, true]]);
+        assert_eq!(point_count, 2 * points_per_ray);
+        image.clone().slice([0..1, 0..1, 0..3]).into_data().assert_approx_eq(
+            &image_true.clone().slice([0..1, 0..1, 0..3]).into_data(),
+            5,
+        );
+        image.clone().slice([2..3, 1..2, 0..3]).into_data().assert_approx_eq(
+            &image_true.slice([2..3, 1..2, 0..3]).into_data(),
+            5,
+        );
+        assert_eq!(
+            image.slice([1..2, 0..2, 0..3]).abs().max().into_scalar(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn volume_renderer_uncertainty() {
+        let device = Default::default();
+
+        let colors = Tensor::<Backend, 2>::from_floats(
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.5, 0.0],
+                [0.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+            ],
+            &device,
+        )
+        .reshape([1, 1, 4, 3])
+        .expand([2, 1, 4, 3]);
+        let weights = Tensor::<Backend, 2>::from_floats(
+            [[0.0, 0.9, 0.0, 0.0], [0.25, 0.25, 0.25, 0.25]],
+            &device,
+        )
+        .reshape([2, 1, 4, 1]);
+        let output = VolumeRendererOutput {
+            accumulated_opacity: weights.clone().sum_dim(2).squeeze::<3>(2),
+            image: (colors.clone() * weights.clone())
+                .sum_dim(2)
+                .squeeze::<3>(2),
+            colors,
+            opacities: weights.clone(),
+            weights,
+        };
+
+        let uncertainty = output.get_uncertainty();
+        assert_eq!(uncertainty.dims(), [2, 1, 1]);
+
+        // The dominant sample has no uncertainty and the spread one has some
+        let uncertainty = uncertainty.into_data().value;
+        assert!(uncertainty[0].abs() < 1e-6);
+        assert!(uncertainty[1] > 0.1);
+    }
+
+    #[test]
+    fn volume_renderer_opacity_stats() {
+        let device = Default::default();
+
+        let renderer = VolumeRendererConfig::new(
+            scene::tests::get_test_config(2, 8).with_density_activation(
+                scene::Activation::Softplus {
+                    beta: 1.0,
+                    shift: 0.0,
+                },
+            ),
+        )
+        .init::<Backend>(&device)
+        .unwrap();
+
+        let shape = [3, 4, 5, 3];
+        let intervals = Tensor::<Backend, 1>::from_floats(
+            [1e-3, 1e-3, 1e-3, 1e-3, 1e9],
+            &device,
+        )
+        .reshape([1, 1, 5, 1])
+        .expand([3, 4, 5, 1]);
+        let outputs = renderer.forward_with_aux(
+            Tensor::random(shape, Distribution::Default, &device),
+            intervals,
+            Tensor::random(shape, Distribution::Default, &device),
+        );
+        assert_eq!(outputs.opacities.dims(), [3, 4, 5, 1]);
+
+        let [min, mean, max] = outputs.get_opacity_stats();
+        assert!([min, mean, max].iter().all(|stat| stat.is_finite()));
+        assert!(min >= 0.0);
+        assert!(min <= mean && mean <= max);
+
+        // The opaque sentinel samples are excluded from the statistics
+        let sentinel_min: f32 = outputs
+            .opacities
+            .slice([0..3, 0..4, 4..5, 0..1])
+            .min()
+            .into_scalar();
+        assert!(sentinel_min > 0.99, "{}", sentinel_min);
+        assert!(max < 0.5, "{}", max);
+    }
+
+    #[test]
+    fn volume_renderer_depth() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [3, 2, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let distances =
+            Tensor::<Backend, 1>::from_floats([2.0, 3.0, 4.0, 5.0], &device)
+                .reshape([1, 1, points_per_ray, 1])
+                .expand([3, 2, points_per_ray, 1]);
+        let intervals =
+            Tensor::<Backend, 1>::from_floats([1.0, 1.0, 1.0, 1e9], &device)
+                .reshape([1, 1, points_per_ray, 1])
+                .expand([3, 2, points_per_ray, 1]);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let config = DepthConfig::new(2.0, 6.0).with_missed_depth(-1.0);
+
+        // A fully transparent ray has the missed depth
+        let output = renderer.render(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+            |outputs| scene::VolumetricSceneOutput {
+                densities: outputs.densities.zeros_like(),
+                ..outputs
+            },
+        );
+        let depth = output.get_depth(distances.clone(), &config);
+        assert_eq!(depth.dims(), [3, 2, 1]);
+        depth.into_data().assert_approx_eq(
+            &Tensor::<Backend, 3>::full([3, 2, 1], -1.0, &device).into_data(),
+            6,
+        );
+
+        // A partially opaque ray has the clamped depth
+        let config = DepthConfig::new(2.5, 6.0).with_opacity_threshold(0.1);
+        let output =
+            renderer.render(directions, intervals, positions, |outputs| {
+                scene::VolumetricSceneOutput {
+                    densities: outputs.densities.ones_like(),
+                    ..outputs
+                }
+            });
+        let depth = output.get_depth(distances, &config);
+        depth.into_data().assert_approx_eq(
+            &Tensor::<Backend, 3>::full([3, 2, 1], 2.5, &device).into_data(),
+            4,
+        );
+    }
+
+    #[test]
+    fn volume_renderer_tiled_output() {
+        let device = Default::default();
+
+        let points_per_ray = 4;
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [11, 7, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals = Tensor::random(
+            [11, 7, points_per_ray, 1],
+            Distribution::Default,
+            &device,
+        );
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+
+        let image = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+        for (tile_size, tile_overlap) in [(4, 0), (4, 2), (3, 5), (16, 1)] {
+            let image_tiled = renderer.forward_tiled(
+                directions.clone(),
+                intervals.clone(),
+                positions.clone(),
+                tile_size,
+                tile_overlap,
+            );
+            assert_eq!(image_tiled.dims(), [11, 7, 3]);
+            image_tiled
+                .into_data()
+                .assert_approx_eq(&image.clone().into_data(), 5);
+        }
+
+        // The streamed tiles cover the image once and agree with it
+        let tiles = renderer
+            .forward_tiles(directions, intervals, positions, 4, 1)
+            .collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[5].0,
+            TileRect {
+                bottom: 11,
+                left: 4,
+                right: 7,
+                top: 8,
+            }
+        );
+        let image_streamed = tiles.into_iter().fold(
+            Tensor::zeros([11, 7, 3], &device),
+            |image_streamed, (rect, tile)| {
+                image_streamed.slice_assign(
+                    [rect.top..rect.bottom, rect.left..rect.right, 0..3],
+                    tile,
+                )
+            },
+        );
+        image_streamed.into_data().assert_approx_eq(&image.into_data(), 5);
+    }
+
+    #[test]
+    fn volume_renderer_density_noise() {
+        type Backend = burn::backend::Autodiff<burn::backend::NdArray>;
+
+        let device = Default::default();
+
+        let points_per_ray = 8;
+        let mut renderer = VolumeRendererConfig::new(
+            scene::tests::get_test_config(2, 8).with_density_activation(
+                scene::Activation::Softplus {
+                    beta: 1.0,
+                    shift: 0.0,
+                },
+            ),
+        )
+        .init::<Backend>(&device)
+        .unwrap();
+
+        let shape = [6, 5, points_per_ray, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals = Tensor::random(
+            [6, 5, points_per_ray, 1],
+            Distribution::Default,
+            &device,
+        );
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let render_training = |renderer: &VolumeRenderer<Backend>| {
+            renderer
+                .forward(
+                    directions.clone(),
+                    intervals.clone(),
+                    positions.clone(),
+                )
+                .into_data()
+        };
+        let render_inference = |renderer: &VolumeRenderer<Backend>| {
+            renderer
+                .valid()
+                .forward(
+                    directions.clone().inner(),
+                    intervals.clone().inner(),
+                    positions.clone().inner(),
+                )
+                .into_data()
+        };
+
+        // The output is unchanged without noise
+        let image = render_inference(&renderer);
+        assert_eq!(render_training(&renderer), render_training(&renderer));
+        render_training(&renderer).assert_approx_eq(&image, 5);
+
+        // The inference is deterministic regardless of the noise
+        renderer.density_noise_std = 1.0;
+        assert_ne!(render_training(&renderer), render_training(&renderer));
+        assert_eq!(render_inference(&renderer), image);
+    }
+
+    #[test]
+    fn volume_renderer_output_transform() {
+        let device = Default::default();
+
+        let colors = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.001, 0.01, 0.2, 0.5, 0.8, 1.0],
+            &device,
+        );
+
+        let colors_srgb = ColorTransform::Srgb.forward(colors.clone());
+        assert!(
+            (colors_srgb.clone().into_data().value[4] - 0.7354).abs() < 1e-3
+        );
+        ColorTransform::Srgb
+            .inverse(colors_srgb)
+            .into_data()
+            .assert_approx_eq(&colors.clone().into_data(), 5);
+
+        let colors_gamma = ColorTransform::Gamma(2.2).forward(colors.clone());
+        ColorTransform::Gamma(2.2)
+            .inverse(colors_gamma)
+            .into_data()
+            .assert_approx_eq(&colors.into_data(), 5);
+
+        // The transform applies to the composited image
+        let mut renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let shape = [3, 4, 5, 3];
+        let directions = Tensor::random(shape, Distribution::Default, &device);
+        let intervals =
+            Tensor::random([3, 4, 5, 1], Distribution::Default, &device);
+        let positions = Tensor::random(shape, Distribution::Default, &device);
+        let image = renderer.forward(
+            directions.clone(),
+            intervals.clone(),
+            positions.clone(),
+        );
+
+        renderer.output_transform = ColorTransform::Srgb;
+        renderer
+            .forward(directions, intervals, positions)
+            .into_data()
+            .assert_approx_eq(
+                &ColorTransform::Srgb.forward(image).into_data(),
+                5,
+            );
+    }
+
+    #[test]
+    fn volume_renderer_interpolation() {
+        let device = Default::default();
+
+        let config =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8));
+        let a = config.init::<Backend>(&device).unwrap();
+        let b = config.init::<Backend>(&device).unwrap();
+
+        let get_parameters = |renderer: &VolumeRenderer<Backend>| {
+            ParametersCollector::collect(renderer)
+                .parameters
+                .into_iter()
+                .flat_map(|parameter| parameter.into_data().value)
+                .collect::<Vec<f32>>()
+        };
+        let parameters_a = get_parameters(&a);
+        let parameters_b = get_parameters(&b);
+        assert_ne!(parameters_a, parameters_b);
+
+        let renderer = VolumeRenderer::lerp(&a, &a, 0.5).unwrap();
+        assert_eq!(get_parameters(&renderer), parameters_a);
+
+        let renderer = VolumeRenderer::lerp(&a, &b, 0.0).unwrap();
+        assert_eq!(get_parameters(&renderer), parameters_a);
+
+        let renderer = VolumeRenderer::lerp(&a, &b, 1.0).unwrap();
+        assert_eq!(get_parameters(&renderer), parameters_b);
+
+        let c = VolumeRendererConfig::new(scene::tests::get_test_config(2, 16))
+            .init::<Backend>(&device)
+            .unwrap();
+        assert!(VolumeRenderer::lerp(&a, &c, 0.5).is_err());
+    }
+
+    #[test]
+    fn volume_renderer_mesh_extraction() {
+        let device = Default::default();
+
+        let renderer =
+            VolumeRendererConfig::new(scene::tests::get_test_config(2, 8))
+                .init::<Backend>(&device)
+                .unwrap();
+
+        let mesh = renderer.extract_mesh(6, ([-1.0; 3], [1.0; 3]), 0.0);
+        assert!(mesh.triangles.iter().all(|triangle| triangle
+            .iter()
+            .all(|&index| (index as usize) < mesh.vertices.len())));
+        assert!(mesh.vertices.iter().all(|vertex| vertex
+            .iter()
+            .all(|&value| (-1.0..=1.0).contains(&value))));
+    }
+}
@@ -0,0 +1,96 @@
+use burn::prelude::*;
+
+// NOTE: The near plane of the LLFF-style scenes, whose bounds are scaled
+// so that nothing is closer to the cameras than the plane at `z = -1`
+pub const NDC_NEAR: f32 = 1.0;
+
+// NOTE: The rays are `[N, 3]` for the forward-facing cameras looking along
+// `-z`. Each origin is moved onto the near plane at `z = -near`, and then
+// the rays are mapped into the normalized device coordinates, where the
+// depths from the near plane to the infinity are in `[-1, 1]`. So the
+// sampling distances of the mapped rays are in `[0, 1]`
+pub fn get_ndc_rays<B: Backend>(
+    origins: Tensor<B, 2>,
+    directions: Tensor<B, 2>,
+    [height, width]: [usize; 2],
+    focal: f32,
+    near: f32,
+) -> (Tensor<B, 2>, Tensor<B, 2>) {
+    let [count, _] = origins.dims();
+    let get_axis = |tensor: &Tensor<B, 2>, axis: usize| {
+        tensor.clone().slice([0..count, axis..axis + 1])
+    };
+
+    let shifts = -(get_axis(&origins, 2) + near) / get_axis(&directions, 2);
+    let origins = origins + directions.clone() * shifts;
+
+    let [o_x, o_y, o_z] = [0, 1, 2].map(|axis| get_axis(&origins, axis));
+    let [d_x, d_y, d_z] = [0, 1, 2].map(|axis| get_axis(&directions, axis));
+    let scale_x = -2.0 * focal / width as f32;
+    let scale_y = -2.0 * focal / height as f32;
+    let o_x = o_x / o_z.clone();
+    let o_y = o_y / o_z.clone();
+    let o_z = o_z.recip();
+
+    let origins = Tensor::cat(
+        vec![
+            o_x.clone() * scale_x,
+            o_y.clone() * scale_y,
+            o_z.clone() * (2.0 * near) + 1.0,
+        ],
+        1,
+    );
+    let directions = Tensor::cat(
+        vec![
+            (d_x / d_z.clone() - o_x) * scale_x,
+            (d_y / d_z - o_y) * scale_y,
+            o_z * (-2.0 * near),
+        ],
+        1,
+    );
+
+    (origins, directions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Backend = burn::backend::NdArray;
+
+    #[test]
+    fn ndc_rays_at_near_plane() {
+        let device = Default::default();
+
+        // The origin on the near plane maps to the NDC depth -1,
+        // and the infinity along the ray maps to the NDC depth 1
+        let origins =
+            Tensor::<Backend, 2>::from_floats([[0.5, 0.25, -1.0]], &device);
+        let directions =
+            Tensor::<Backend, 2>::from_floats([[0.0, 0.0, -1.0]], &device);
+        let (origins_ndc, directions_ndc) =
+            get_ndc_rays(origins, directions, [40, 50], 25.0, NDC_NEAR);
+        origins_ndc
+            .clone()
+            .into_data()
+            .assert_approx_eq(&Data::from([[0.5, 0.3125, -1.0]]), 5);
+        directions_ndc
+            .clone()
+            .into_data()
+            .assert_approx_eq(&Data::from([[-0.5, -0.3125, 2.0]]), 5);
+        (origins_ndc + directions_ndc)
+            .into_data()
+            .assert_approx_eq(&Data::from([[0.0, 0.0, 1.0]]), 5);
+
+        // The origin behind the near plane is moved onto it first
+        let origins =
+            Tensor::<Backend, 2>::from_floats([[0.0, 0.0, 1.0]], &device);
+        let directions =
+            Tensor::<Backend, 2>::from_floats([[0.1, 0.0, -0.5]], &device);
+        let (origins_ndc, _) =
+            get_ndc_rays(origins, directions, [40, 50], 25.0, NDC_NEAR);
+        origins_ndc
+            .into_data()
+            .assert_approx_eq(&Data::from([[0.4, 0.0, -1.0]]), 5);
+    }
+}
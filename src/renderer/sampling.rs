@@ -0,0 +1,165 @@
+use burn::{prelude::*, tensor::Distribution};
+
+// NOTE: The samples are drawn by the inverse transform of the piecewise
+// constant PDF over the bins. `bins` has the `B + 1` edges of the `B` bins
+// for each row, and the rows with zero weights are sampled uniformly.
+// The deterministic samples are at the midpoints of the `n_samples` strata
+pub fn sample_pdf<B: Backend>(
+    bins: Tensor<B, 2>,
+    weights: Tensor<B, 2>,
+    n_samples: usize,
+    deterministic: bool,
+) -> Tensor<B, 2> {
+    let [row_count, bin_count] = weights.dims();
+    let device = weights.device();
+
+    // Normalizing the Weights
+    let weights = weights.clamp_min(0.0);
+    let weight_sums = weights.clone().sum_dim(1);
+    let is_empty = weight_sums.clone().lower_equal_elem(1e-12);
+    let weights =
+        weights.mask_fill(is_empty.clone().expand([row_count, bin_count]), 1.0);
+    let weight_sums = weight_sums.mask_fill(is_empty, bin_count as f32);
+    let pdf = weights / weight_sums;
+
+    // NOTE: The CDF starts from zero, where the `j`-th value sums
+    // the PDF values before the `j`-th edge
+    let cdf = {
+        let rows = Tensor::<B, 1, Int>::arange(0..bin_count as i64, &device)
+            .reshape([bin_count, 1]);
+        let columns =
+            Tensor::<B, 1, Int>::arange(0..bin_count as i64 + 1, &device)
+                .reshape([1, bin_count + 1]);
+        let triangle = rows
+            .expand([bin_count, bin_count + 1])
+            .lower(columns.expand([bin_count, bin_count + 1]))
+            .float();
+        pdf.matmul(triangle)
+    };
+
+    let samples = if deterministic {
+        (Tensor::<B, 1, Int>::arange(0..n_samples as i64, &device).float()
+            + 0.5)
+            .div_scalar(n_samples as f32)
+            .reshape([1, n_samples])
+            .expand([row_count, n_samples])
+    } else {
+        Tensor::random(
+            [row_count, n_samples],
+            Distribution::Uniform(0.0, 1.0),
+            &device,
+        )
+    };
+
+    // NOTE: The index is the count of the CDF values not above the sample
+    let indexs = cdf
+        .clone()
+        .reshape([row_count, 1, bin_count + 1])
+        .expand([row_count, n_samples, bin_count + 1])
+        .lower_equal(
+            samples.clone().reshape([row_count, n_samples, 1]).expand([
+                row_count,
+                n_samples,
+                bin_count + 1,
+            ]),
+        )
+        .int()
+        .sum_dim(2)
+        .reshape([row_count, n_samples]);
+    let indexs_below = (indexs.clone() - 1).clamp(0, bin_count as i64);
+    let indexs_above = indexs.clamp(0, bin_count as i64);
+
+    let cdf_below = cdf.clone().gather(1, indexs_below.clone());
+    let cdf_above = cdf.gather(1, indexs_above.clone());
+    let bins_below = bins.clone().gather(1, indexs_below);
+    let bins_above = bins.gather(1, indexs_above);
+
+    let denominators = cdf_above - cdf_below.clone();
+    let denominators =
+        denominators.clone().mask_fill(denominators.lower_elem(1e-5), 1.0);
+    let ratios = (samples - cdf_below) / denominators;
+
+    bins_below.clone() + (bins_above - bins_below) * ratios
+}
+
+// NOTE: The intervals are between the consecutive distances of each row,
+// and the last one is the sentinel extending the ray to the infinity
+pub fn get_intervals<B: Backend>(distances: Tensor<B, 2>) -> Tensor<B, 2> {
+    let [row_count, points_per_ray] = distances.dims();
+    let device = distances.device();
+
+    Tensor::cat(
+        vec![
+            distances.clone().slice([0..row_count, 1..points_per_ray])
+                - distances.slice([0..row_count, 0..points_per_ray - 1]),
+            Tensor::full([row_count, 1], 1e9, &device),
+        ],
+        1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Backend = burn::backend::NdArray;
+
+    #[test]
+    fn sample_pdf_delta_weights() {
+        let device = Default::default();
+
+        let bins = Tensor::<Backend, 1, Int>::arange(0..9, &device)
+            .float()
+            .div_scalar(8.0)
+            .reshape([1, 9])
+            .repeat(0, 2);
+        let weights = Tensor::<Backend, 1>::from_floats(
+            [0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0],
+            &device,
+        )
+        .reshape([1, 8])
+        .repeat(0, 2);
+
+        for deterministic in [true, false] {
+            let samples =
+                sample_pdf(bins.clone(), weights.clone(), 16, deterministic);
+            assert_eq!(samples.dims(), [2, 16]);
+            let samples = samples.into_data().convert::<f32>().value;
+            assert!(
+                samples.iter().all(|sample| (0.375..=0.5).contains(sample)),
+                "{:?}",
+                samples
+            );
+        }
+
+        // The samples of the deterministic strata are evenly spaced
+        let samples = sample_pdf(bins.clone(), weights, 4, true)
+            .into_data()
+            .convert::<f32>();
+        Data::<f32, 1>::from(&samples.value[..4]).assert_approx_eq(
+            &Data::from([0.390625, 0.421875, 0.453125, 0.484375]),
+            5,
+        );
+
+        // The samples are uniform over the bins for the zero weights
+        let samples = sample_pdf(bins, Tensor::zeros([2, 8], &device), 4, true)
+            .into_data()
+            .convert::<f32>();
+        Data::<f32, 1>::from(&samples.value[..4])
+            .assert_approx_eq(&Data::from([0.125, 0.375, 0.625, 0.875]), 5);
+    }
+
+    #[test]
+    fn get_intervals_sentinel() {
+        let device = Default::default();
+
+        let distances = Tensor::<Backend, 2>::from_floats(
+            [[2.0, 3.0, 4.0, 5.0], [2.5, 3.0, 4.5, 5.5]],
+            &device,
+        );
+        get_intervals(distances).into_data().assert_approx_eq(
+            &Data::from([[1.0, 1.0, 1.0, 1e9], [0.5, 1.5, 1.0, 1e9]]),
+            5,
+        );
+    }
+}
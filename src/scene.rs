@@ -1,42 +1,103 @@
 use crate::*;
 use anyhow::Result;
-use burn::{prelude::*, tensor::activation};
+use burn::{
+    constant,
+    prelude::*,
+    tensor::{activation, Distribution},
+};
+use encoder::InputEncoder;
 
 #[derive(Config, Debug)]
 pub struct VolumetricSceneConfig {
+    // NOTE: The count matches the image channels of the dataset,
+    // which is 1 for grayscale images
+    #[config(default = 3)]
+    pub color_channel_count: usize,
+    #[config(default = "Activation::Relu")]
+    pub density_activation: Activation,
+    pub direction_encoder: encoder::EncoderConfig,
+    #[config(default = "Activation::Relu")]
+    pub hidden_activation: Activation,
     pub hidden_size: usize,
-    pub input_encoder: encoder::PositionalEncoderConfig,
+    // NOTE: The default is the initializer of `nn::LinearConfig`
+    #[config(
+        default = "nn::Initializer::KaimingUniform { gain: 1.0 / 3.0_f64.sqrt(), fan_out_only: false }"
+    )]
+    pub initializer: nn::Initializer,
+    pub position_encoder: encoder::EncoderConfig,
+}
+
+#[derive(Config, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Gelu,
+    Relu,
+    Silu,
+    // NOTE: It is `log(1 + exp(beta * (x - shift))) / beta`, which is
+    // `log(1 + exp(-beta * shift)) / beta` at zero. The shift is 1 for the
+    // densities in mip-NeRF, so the initial densities are less saturated
+    Softplus {
+        beta: f32,
+        shift: f32,
+    },
+}
+
+constant!(Activation);
+
+#[derive(Clone, Debug)]
+pub struct VolumetricSceneOutput<B: Backend, const D: usize> {
+    pub colors: Tensor<B, D>,
+    pub densities: Tensor<B, D>,
 }
 
 #[derive(Debug, Module)]
 pub struct VolumetricScene<B: Backend> {
-    input_encoder: encoder::PositionalEncoder<B>,
+    direction_encoder: encoder::Encoder<B>,
+    hidden_activation: Activation,
     hidden_layers: Vec<nn::Linear<B>>,
+    density_activation: Activation,
     output_layer: nn::Linear<B>,
+    position_encoder: encoder::Encoder<B>,
     skip_indexs: Vec<usize>,
 }
 
 impl VolumetricSceneConfig {
+    // NOTE: Sharing one encoder config for both positions and directions
+    pub fn from_input_encoder(
+        hidden_size: usize,
+        input_encoder: encoder::EncoderConfig,
+    ) -> Self {
+        Self::new(input_encoder.clone(), hidden_size, input_encoder)
+    }
+
     pub fn init<B: Backend>(
         &self,
         device: &B::Device,
     ) -> Result<VolumetricScene<B>> {
-        let i = self.input_encoder.get_output_size(6);
+        let i = self.direction_encoder.get_output_size(3)
+            + self.position_encoder.get_output_size(3);
         let h = self.hidden_size;
-        let o = 3 + 1;
+        let o = self.color_channel_count + 1;
+        let linear = |input_size, output_size| {
+            nn::LinearConfig::new(input_size, output_size)
+                .with_initializer(self.initializer.clone())
+                .init(device)
+        };
         Ok(VolumetricScene {
-            input_encoder: self.input_encoder.init(device)?,
+            direction_encoder: self.direction_encoder.init(device)?,
+            hidden_activation: self.hidden_activation,
             hidden_layers: vec![
-                nn::LinearConfig::new(i, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
-                nn::LinearConfig::new(h + i, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
-                nn::LinearConfig::new(h, h).init(device),
+                linear(i, h),
+                linear(h, h),
+                linear(h, h),
+                linear(h, h),
+                linear(h, h),
+                linear(h + i, h),
+                linear(h, h),
+                linear(h, h),
             ],
-            output_layer: nn::LinearConfig::new(h, o).init(device),
+            density_activation: self.density_activation,
+            output_layer: linear(h, o),
+            position_encoder: self.position_encoder.init(device)?,
             skip_indexs: vec![5],
         })
     }
@@ -47,10 +108,151 @@ impl<B: Backend> VolumetricScene<B> {
         &self,
         directions: Tensor<B, 2>,
         positions: Tensor<B, 2>,
+    ) -> VolumetricSceneOutput<B, 2> {
+        self.forward_with_density_noise(directions, positions, 0.0)
+    }
+
+    pub fn query_point(
+        &self,
+        position: [f32; 3],
+        direction: [f32; 3],
+        device: &B::Device,
+    ) -> ([f32; 3], f32) {
+        let outputs = self.forward(
+            Tensor::<B, 1>::from_floats(direction, device).unsqueeze(),
+            Tensor::<B, 1>::from_floats(position, device).unsqueeze(),
+        );
+        let colors = outputs.colors.into_data().convert::<f32>().value;
+        let density = outputs.densities.into_scalar().elem::<f32>();
+
+        // NOTE: The grayscale color is broadcast to all the channels
+        let color = |channel: usize| colors[channel.min(colors.len() - 1)];
+        ([color(0), color(1), color(2)], density)
+    }
+
+    // NOTE: The size counts the inputs and the outputs of every layer
+    // for one sample
+    pub fn get_activation_size(&self) -> usize {
+        self.hidden_layers
+            .iter()
+            .chain([&self.output_layer])
+            .map(|layer| layer.weight.dims().iter().sum::<usize>())
+            .sum()
+    }
+
+    // NOTE: Each multiply-add of the layers counts as 2 FLOPs for one sample,
+    // and the encoders and the activations are ignored
+    pub fn get_flop_count(&self) -> usize {
+        self.hidden_layers
+            .iter()
+            .chain([&self.output_layer])
+            .map(|layer| 2 * layer.weight.dims().iter().product::<usize>())
+            .sum()
+    }
+
+    pub fn get_color_channel_count(&self) -> usize {
+        self.output_layer.weight.dims()[1] - 1
+    }
+
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "direction_encoder: 3 -> {}",
+                self.direction_encoder.get_output_size(3)
+            ),
+            format!(
+                "position_encoder: 3 -> {}",
+                self.position_encoder.get_output_size(3)
+            ),
+        ];
+        for (index, layer) in self.hidden_layers.iter().enumerate() {
+            let [input_size, output_size] = layer.weight.dims();
+            lines.push(format!(
+                "hidden_layers.{}: {} -> {}",
+                index, input_size, output_size
+            ));
+        }
+        let [input_size, output_size] = self.output_layer.weight.dims();
+        lines.push(format!("output_layer: {} -> {}", input_size, output_size));
+        lines.push(format!("parameters: {}", self.num_params()));
+
+        lines.join("\n")
+    }
+
+    // NOTE: The directions are zeros as in the mesh extraction, and only
+    // the density column of the output layer is evaluated
+    pub fn forward_density(
+        &self,
+        positions: Tensor<B, 2>,
+    ) -> Tensor<B, 1> {
+        let features = self.get_features(positions.zeros_like(), positions);
+        let [hidden_size, output_size] = self.output_layer.weight.dims();
+        let color_channel_count = output_size - 1;
+        let weight = self
+            .output_layer
+            .weight
+            .val()
+            .slice([0..hidden_size, color_channel_count..output_size]);
+        let densities = match &self.output_layer.bias {
+            Some(bias) => {
+                features.matmul(weight)
+                    + bias
+                        .val()
+                        .unsqueeze::<2>()
+                        .slice([0..1, color_channel_count..output_size])
+            },
+            None => features.matmul(weight),
+        };
+
+        self.density_activation.forward(densities).squeeze(1)
+    }
+
+    pub fn forward_with_density_noise(
+        &self,
+        directions: Tensor<B, 2>,
+        positions: Tensor<B, 2>,
+        density_noise_std: f32,
+    ) -> VolumetricSceneOutput<B, 2> {
+        let features = self.get_features(directions, positions);
+        let features = self.output_layer.forward(features);
+        let [size, output_size] = features.dims();
+        let color_channel_count = output_size - 1;
+        let colors = activation::sigmoid(
+            features.clone().slice([0..size, 0..color_channel_count]),
+        );
+        let densities = {
+            let densities =
+                features.slice([0..size, color_channel_count..output_size]);
+            let densities = if density_noise_std > 0.0 {
+                densities.clone()
+                    + densities.random_like(Distribution::Normal(
+                        0.0,
+                        density_noise_std as f64,
+                    ))
+            } else {
+                densities
+            };
+            self.density_activation.forward(densities)
+        };
+
+        VolumetricSceneOutput {
+            colors,
+            densities,
+        }
+    }
+
+    fn get_features(
+        &self,
+        directions: Tensor<B, 2>,
+        positions: Tensor<B, 2>,
     ) -> Tensor<B, 2> {
-        let inputs = self
-            .input_encoder
-            .forward(Tensor::cat(vec![directions, positions], 1));
+        let inputs = Tensor::cat(
+            vec![
+                self.direction_encoder.forward(directions),
+                self.position_encoder.forward(positions),
+            ],
+            1,
+        );
         let mut features = inputs.clone();
 
         for (index, layer) in self.hidden_layers.iter().enumerate() {
@@ -58,37 +260,82 @@ impl<B: Backend> VolumetricScene<B> {
                 features = Tensor::cat(vec![features, inputs.clone()], 1);
             }
             features = layer.forward(features);
-            features = activation::relu(features);
+            features = self.hidden_activation.forward(features);
         }
 
-        let outputs = {
-            features = self.output_layer.forward(features);
-            let size = features.dims()[0];
-            let colors =
-                activation::sigmoid(features.clone().slice([0..size, 0..3]));
-            let densities = activation::relu(features.slice([0..size, 3..4]));
-
-            Tensor::cat(vec![colors, densities], 1)
-        };
+        features
+    }
+}
 
-        outputs
+impl Activation {
+    pub fn forward<B: Backend, const D: usize>(
+        &self,
+        tensor: Tensor<B, D>,
+    ) -> Tensor<B, D> {
+        match self {
+            Activation::Gelu => activation::gelu(tensor),
+            Activation::Relu => activation::relu(tensor),
+            Activation::Silu => activation::silu(tensor),
+            Activation::Softplus {
+                beta,
+                shift,
+            } => activation::softplus(tensor - *shift, *beta as f64),
+        }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use burn::tensor::Distribution;
+    use std::sync::Mutex;
+
+    type Backend = burn::backend::NdArray;
+
+    // NOTE: The positions and the directions share the positional encoder,
+    // and the other options are the defaults
+    pub(crate) fn get_test_config(
+        encoding_factor: usize,
+        hidden_size: usize,
+    ) -> VolumetricSceneConfig {
+        VolumetricSceneConfig::from_input_encoder(
+            hidden_size,
+            encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig::new(encoding_factor),
+            ),
+        )
+    }
 
-    type Backend = burn::backend::Wgpu;
+    // NOTE: The backend seed is global to the process, so the tests seeding
+    // it are serialized by this lock, and each one compares two seeded
+    // initializations made while holding it
+    pub(crate) static SEED_LOCK: Mutex<()> = Mutex::new(());
 
     #[test]
     fn volumetric_scene_output_shape() {
         let config = VolumetricSceneConfig {
+            color_channel_count: 3,
+            density_activation: Activation::Relu,
+            hidden_activation: Activation::Relu,
             hidden_size: 8,
-            input_encoder: encoder::PositionalEncoderConfig {
-                encoding_factor: 1,
+            initializer: nn::Initializer::KaimingUniform {
+                gain: 1.0 / 3.0_f64.sqrt(),
+                fan_out_only: false,
             },
+            direction_encoder: encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig {
+                    encoding_factor: 1,
+                    frequency_spacing: encoder::FrequencySpacing::Log2,
+                    include_input: true,
+                },
+            ),
+            position_encoder: encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig {
+                    encoding_factor: 3,
+                    frequency_spacing: encoder::FrequencySpacing::Log2,
+                    include_input: true,
+                },
+            ),
         };
         let device = Default::default();
 
@@ -99,6 +346,176 @@ mod tests {
         let directions = positions.random_like(Distribution::Default);
 
         let outputs = model.forward(positions, directions);
-        assert_eq!(outputs.dims(), [1234, 4]);
+        assert_eq!(outputs.colors.dims(), [1234, 3]);
+        assert_eq!(outputs.densities.dims(), [1234, 1]);
+        assert_eq!(model.hidden_layers[0].weight.dims(), [3 * 3 + 3 * 7, 8]);
+    }
+
+    #[test]
+    fn volumetric_scene_softplus_density() {
+        let config = VolumetricSceneConfig::from_input_encoder(
+            8,
+            encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig {
+                    encoding_factor: 1,
+                    frequency_spacing: encoder::FrequencySpacing::Log2,
+                    include_input: true,
+                },
+            ),
+        )
+        .with_density_activation(Activation::Softplus {
+            beta: 1.0,
+            shift: 1.0,
+        })
+        .with_hidden_activation(Activation::Silu);
+        let device = Default::default();
+
+        let model = config.init::<Backend>(&device).unwrap();
+
+        let positions = Tensor::random(
+            [1234, 3],
+            Distribution::Uniform(-4.0, 4.0),
+            &device,
+        );
+        let directions = positions.random_like(Distribution::Default);
+
+        let outputs = model.forward(directions, positions);
+        assert!(outputs.densities.greater_elem(0.0).all().into_scalar());
+
+        let config =
+            VolumetricSceneConfig::load_binary(config.to_string().as_bytes())
+                .unwrap();
+        assert_eq!(
+            config.density_activation,
+            Activation::Softplus {
+                beta: 1.0,
+                shift: 1.0,
+            }
+        );
+        assert_eq!(config.hidden_activation, Activation::Silu);
+    }
+
+    #[test]
+    fn activation_shifted_softplus() {
+        let device = Default::default();
+
+        let input = Tensor::<Backend, 1>::zeros([1], &device);
+        let get_output = |beta, shift| {
+            Activation::Softplus {
+                beta,
+                shift,
+            }
+            .forward(input.clone())
+            .into_scalar()
+        };
+        for (beta, shift) in [(1.0, 0.0), (1.0, 1.0), (2.0, 1.0)] {
+            let output = get_output(beta, shift);
+            let output_expected = (1.0 + (-beta * shift).exp()).ln() / beta;
+            assert!((output - output_expected).abs() < 1e-6, "{}", output);
+        }
+        assert!((get_output(1.0, 1.0) - 0.31326166).abs() < 1e-6);
+    }
+
+    #[test]
+    fn volumetric_scene_query_point() {
+        let config = VolumetricSceneConfig::from_input_encoder(
+            8,
+            encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig {
+                    encoding_factor: 2,
+                    frequency_spacing: encoder::FrequencySpacing::Log2,
+                    include_input: true,
+                },
+            ),
+        );
+        let device = Default::default();
+
+        let model = config.init::<Backend>(&device).unwrap();
+
+        let position = [0.1, -0.2, 0.3];
+        let direction = [0.0, 0.0, -1.0];
+        let (color, density) = model.query_point(position, direction, &device);
+        assert_eq!(
+            model.query_point(position, direction, &device),
+            (color, density)
+        );
+        assert!(color.iter().all(|value| (0.0..=1.0).contains(value)));
+        assert!(density >= 0.0);
+
+        let outputs = model.forward(
+            Tensor::from_floats([direction], &device),
+            Tensor::from_floats([position], &device),
+        );
+        assert_eq!(outputs.colors.into_data().value, color);
+        assert_eq!(outputs.densities.into_data().value, [density]);
+    }
+
+    fn get_initializer_test_weights(
+        config: &VolumetricSceneConfig
+    ) -> Vec<f32> {
+        let scene = config.init::<Backend>(&Default::default()).unwrap();
+        scene
+            .hidden_layers
+            .iter()
+            .chain([&scene.output_layer])
+            .flat_map(|layer| layer.weight.val().into_data().value)
+            .collect()
+    }
+
+    #[test]
+    fn volumetric_scene_initializer() {
+        let config = get_test_config(2, 8);
+        assert_eq!(config.initializer, nn::LinearConfig::new(1, 1).initializer);
+
+        // The seeded weights are reproducible
+        {
+            use burn::tensor::backend::Backend as _;
+
+            let _lock = SEED_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let get_seeded_weights = || {
+                Backend::seed(3);
+                get_initializer_test_weights(&config)
+            };
+            assert_eq!(get_seeded_weights(), get_seeded_weights());
+        }
+
+        let config = config.with_initializer(nn::Initializer::Uniform {
+            min: -1e-3,
+            max: 1e-3,
+        });
+        assert!(get_initializer_test_weights(&config)
+            .iter()
+            .all(|weight| (-1e-3..=1e-3).contains(weight)));
+    }
+
+    #[test]
+    fn volumetric_scene_density_only() {
+        let config = VolumetricSceneConfig::from_input_encoder(
+            8,
+            encoder::EncoderConfig::Positional(
+                encoder::PositionalEncoderConfig {
+                    encoding_factor: 2,
+                    frequency_spacing: encoder::FrequencySpacing::Log2,
+                    include_input: true,
+                },
+            ),
+        )
+        .with_density_activation(Activation::Softplus {
+            beta: 1.0,
+            shift: 1.0,
+        });
+        let device = Default::default();
+
+        let model = config.init::<Backend>(&device).unwrap();
+
+        let positions =
+            Tensor::random([321, 3], Distribution::Uniform(-4.0, 4.0), &device);
+        let densities = model.forward_density(positions.clone());
+        assert_eq!(densities.dims(), [321]);
+
+        let outputs = model.forward(positions.zeros_like(), positions);
+        densities
+            .into_data()
+            .assert_approx_eq(&outputs.densities.reshape([321]).into_data(), 5);
     }
 }